@@ -1,18 +1,25 @@
 use disruptor::{Producer, Sequence};
 use ibag::iBag;
-use log::info;
+use log::{info, warn};
 use rioc::{LayerChain, LayerResult, PayLoad, SharedLayer};
 use serde_json::Value;
 use std::{
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
     time::Duration,
 };
 
 use crate::schema::{client::{build_client_notification, build_client_request}, schema::{ListToolsResult, LoggingLevel, SetLevelParams, SetLevelRequest}};
 use crate::schema::json_rpc::mcp_json_param;
+use crate::transport::read_inbound;
 use crate::schema::schema::{
     CallToolParams, CallToolRequest, ClientNotification, Cursor, InitializedNotification,
-    InitializedNotificationParams, ListToolsRequest, PaginatedParams,
+    ArgumentInfo, CompleteParams, CreateMessageParams, CreateMessageResult, JSONRPCError,
+    JSONRPCErrorObject, JSONRPCResponse, ListRootsResult, Root, RootsListChangedNotification, error_codes, CompleteRequest, CompleteResult, GetPromptParams, GetPromptRequest, GetPromptResult, InitializedNotificationParams,
+    ListPromptsRequest, ListPromptsResult, ListResourceTemplatesRequest, ListResourceTemplatesResult,
+    ListResourcesRequest, ListResourcesResult, ListToolsRequest, PaginatedParams,
+    ReadResourceParams, ReadResourceRequest, ReadResourceResult, Reference, SubscribeParams, SubscribeRequest,
+    UnsubscribeParams, UnsubscribeRequest,
 };
 use crate::{
     schema::schema::{
@@ -29,9 +36,30 @@ use crate::{
 
 pub trait ClientProvider {
     fn client_ping_response(&self, id: RequestId, _params: Option<Value>) -> Result<(), MCPError>;
+    /// Runs before the roots set with `Client::set_roots` are returned, an error fails the request.
     fn client_list_roots(&self, id: RequestId, _params: Option<Value>) -> Result<(), MCPError>;
+    /// Runs before `client_create_message`, an error declines the sampling request.
     fn client_sampling_message(&self, id: RequestId, _params: Option<Value>) -> Result<(), MCPError>;
     fn client_logs(&self,params: Option<Value>) -> Result<(), MCPError>;
+
+    /// A subscribed resource changed, `params` carries its uri.
+    fn client_resource_updated(&self, _params: Option<Value>) -> Result<(), MCPError> {
+        Ok(())
+    }
+
+    fn client_resource_list_changed(&self) -> Result<(), MCPError> {
+        Ok(())
+    }
+
+    /// Progress of a request sent with a progress token.
+    fn client_progress(&self, _params: Option<Value>) -> Result<(), MCPError> {
+        Ok(())
+    }
+
+    /// Sample an LLM for the server, only asked when sampling is enabled on the client.
+    fn client_create_message(&self, _params: CreateMessageParams) -> Result<CreateMessageResult, MCPError> {
+        Err(MCPError::UnsupportedFeature("sampling".to_string()))
+    }
 }
 
 
@@ -44,6 +72,10 @@ pub struct Client<T: ClientProvider + Default + Clone + Send + 'static> {
     disruptor: Option<DisruptorWriter>,
     cached: Arc<Mutex<Vec<JSONRPCMessage>>>,
     current_request_id: Option<i64>,
+    sampling: bool,
+    roots: Arc<Mutex<Vec<Root>>>,
+    //set once initialize succeeded, notifications are only sent afterwards
+    session_active: Arc<AtomicBool>,
     provider: T,
 }
 
@@ -57,6 +89,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
             disruptor: None,
             cached: Arc::new(Mutex::new(Vec::new())),
             current_request_id: None,
+            sampling: false,
+            roots: Arc::new(Mutex::new(Vec::new())),
+            session_active: Arc::new(AtomicBool::new(false)),
             provider: T::default(),
         }
     }
@@ -71,9 +106,8 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
     }
 
     pub fn serve(&self) -> Result<(), MCPError> {
-        let _ = self.handle_inbound();
-        Ok(())
-    } 
+        read_inbound(|| self.handle_inbound()).map(|_| ())
+    }
 
     pub fn start(&mut self) -> Result<(), MCPError> {
         if self.is_initialized {
@@ -128,11 +162,11 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
                     }
                     "roots/list" => {
                         info!("Received roots/list request");
-                        self.provider.client_list_roots(id, params)?;
+                        self.handle_list_roots(id, params)?;
                     }
                     "sampling/createMessage" => {
                         info!("Received sampling/createMessage request");
-                        self.provider.client_sampling_message(id, params)?;
+                        self.handle_create_message(id, params)?;
                     }
                     _ => {
                         info!("Received unsupported method: {}", method);
@@ -144,9 +178,22 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
 
                 Ok(())
             }
-            JSONRPCMessage::Notification(params) => {
-                let params = params.params.clone();
-                let _ = self.provider.client_logs(params);
+            JSONRPCMessage::Notification(notification) => {
+                let params = notification.params.clone();
+                match notification.method.as_str() {
+                    "notifications/resources/updated" => {
+                        let _ = self.provider.client_resource_updated(params);
+                    }
+                    "notifications/resources/list_changed" => {
+                        let _ = self.provider.client_resource_list_changed();
+                    }
+                    "notifications/progress" => {
+                        let _ = self.provider.client_progress(params);
+                    }
+                    _ => {
+                        let _ = self.provider.client_logs(params);
+                    }
+                }
                 Ok(())
             }
             JSONRPCMessage::Error(_) => {
@@ -156,6 +203,83 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         }
     }
 
+    fn handle_create_message(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let result = if !self.sampling {
+            Err(JSONRPCErrorObject {
+                code: error_codes::METHOD_NOT_FOUND,
+                message: "Sampling is not enabled on this client".to_string(),
+                data: None,
+            })
+        } else if let Err(e) = self.provider.client_sampling_message(id.clone(), params.clone()) {
+            Err(JSONRPCErrorObject {
+                code: -1,
+                message: format!("Sampling request declined: {}", e),
+                data: None,
+            })
+        } else {
+            serde_json::from_value::<CreateMessageParams>(params.unwrap_or(Value::Null))
+                .map_err(|e| JSONRPCErrorObject {
+                    code: error_codes::INVALID_PARAMS,
+                    message: format!("Invalid sampling/createMessage parameters: {}", e),
+                    data: None,
+                })
+                .and_then(|params| {
+                    self.provider.client_create_message(params).map_err(|e| JSONRPCErrorObject {
+                        code: error_codes::INTERNAL_ERROR,
+                        message: format!("Sampling failed: {}", e),
+                        data: None,
+                    })
+                })
+        };
+
+        self.respond(id, result)
+    }
+
+    fn handle_list_roots(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let result = match self.provider.client_list_roots(id.clone(), params) {
+            Ok(()) => Ok(ListRootsResult {
+                _meta: None,
+                roots: self.roots.lock().unwrap().clone(),
+            }),
+            Err(e) => Err(JSONRPCErrorObject {
+                code: error_codes::INTERNAL_ERROR,
+                message: format!("Failed to list roots: {}", e),
+                data: None,
+            }),
+        };
+        self.respond(id, result)
+    }
+
+    fn respond<R: serde::Serialize>(&self, id: RequestId, result: Result<R, JSONRPCErrorObject>) -> Result<(), MCPError> {
+        let message = match result {
+            Ok(result) => JSONRPCMessage::Response(JSONRPCResponse::new(id, serde_json::to_value(result)?)),
+            Err(error) => JSONRPCMessage::Error(JSONRPCError::new(id, error)),
+        };
+        let payload = rioc::PayLoad {
+            data: Some(serde_json::to_string(&message)?),
+            ctx: None,
+        };
+        let _ = self.handle_outbound(Some(payload));
+        Ok(())
+    }
+
+    /// Replace the roots answered to roots/list, telling the server when a
+    /// session is already open.
+    pub fn set_roots(&self, roots: Vec<Root>) -> Result<(), MCPError> {
+        *self.roots.lock().unwrap() = roots;
+        if !self.session_active.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let notification = ClientNotification::RootsListChanged(RootsListChangedNotification::new());
+        let notify = build_client_notification(notification);
+        let payload = rioc::PayLoad {
+            data: mcp_json_param(&notify),
+            ctx: None,
+        };
+        self.handle_outbound(Some(payload)).map_err(MCPError::Transport)
+    }
+
     fn handle_unsupported(
         &self,
         id: RequestId,
@@ -170,6 +294,12 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         self
     }
 
+    /// Advertise sampling and answer sampling/createMessage through the provider.
+    pub fn with_sampling(&mut self, enabled: bool) -> &mut Self {
+        self.sampling = enabled;
+        self
+    }
+
     pub fn recieve_with_timeout(&mut self) -> Result<JSONRPCMessage, MCPError> {
         if self.timeout_duration.is_none() {
             //receive forever
@@ -199,6 +329,22 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         }
     }
 
+    /// Wait for the answer to `request_id`, dropping late answers to earlier requests.
+    fn recieve_response(&mut self, request_id: &RequestId) -> Result<JSONRPCMessage, MCPError> {
+        loop {
+            let message = self.recieve_with_timeout()?;
+            let id = match &message {
+                JSONRPCMessage::Response(resp) => &resp.id,
+                JSONRPCMessage::Error(error) => &error.id,
+                _ => return Ok(message),
+            };
+            if id == request_id {
+                return Ok(message);
+            }
+            warn!("Dropping response to stale request {:?}, waiting for {:?}", id, request_id);
+        }
+    }
+
     pub fn try_recieve(&mut self) -> Result<JSONRPCMessage, MCPError> {
         // Check if there is any cached message
         if let Some(message) = self.pop_response() {
@@ -218,9 +364,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
             capabilities: ClientCapabilities {
                 experimental: None,
                 roots: Some(RootsCapability {
-                    list_changed: Some(false),
+                    list_changed: Some(true),
                 }),
-                sampling: None,
+                sampling: if self.sampling { Some(serde_json::json!({})) } else { None },
             },
         };
 
@@ -239,7 +385,7 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         let _ = self.handle_outbound(Some(payload));
 
         //wait for response
-        let response = self.recieve_with_timeout()?;
+        let response = self.recieve_response(&request_id)?;
         match response {
             JSONRPCMessage::Response(response) => {
                 //response with notification
@@ -255,7 +401,7 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
                 //send initial request to server
                 let _ = self.handle_outbound(Some(payload));
 
-                assert!(response.id == request_id);
+                self.session_active.store(true, Ordering::Release);
 
                 Ok(response.result)
             }
@@ -279,10 +425,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         let _ = self.handle_outbound(Some(payload));
 
         //wait for response
-        let response = self.recieve_with_timeout()?;
+        let response = self.recieve_response(&request_id)?;
         match response {
             JSONRPCMessage::Response(resp) => {
-                assert!(resp.id == request_id);
 
                 let result = serde_json::from_value::<ListToolsResult>(resp.result);
                 result.map_err(|e| MCPError::Protocol(format!("Failed to parse ListToolsResult: {:?}", e)))
@@ -292,6 +437,81 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         }
     }
 
+    pub fn list_resources(&mut self, cursor: Option<Cursor>) -> Result<ListResourcesResult, MCPError> {
+        let req = ClientRequest::ListResources(ListResourcesRequest::new(Some(PaginatedParams { cursor })));
+        self.request(req, "ListResourcesResult")
+    }
+
+    pub fn list_resource_templates(&mut self, cursor: Option<Cursor>) -> Result<ListResourceTemplatesResult, MCPError> {
+        let req = ClientRequest::ListResourceTemplates(ListResourceTemplatesRequest::new(Some(PaginatedParams { cursor })));
+        self.request(req, "ListResourceTemplatesResult")
+    }
+
+    pub fn read_resource(&mut self, uri: &str) -> Result<ReadResourceResult, MCPError> {
+        let req = ClientRequest::ReadResource(ReadResourceRequest::new(ReadResourceParams {
+            uri: uri.to_string(),
+        }));
+        self.request(req, "ReadResourceResult")
+    }
+
+    pub fn list_prompts(&mut self, cursor: Option<Cursor>) -> Result<ListPromptsResult, MCPError> {
+        let req = ClientRequest::ListPrompts(ListPromptsRequest::new(Some(PaginatedParams { cursor })));
+        self.request(req, "ListPromptsResult")
+    }
+
+    pub fn get_prompt(&mut self, name: &str, arguments: HashMap<String, String>) -> Result<GetPromptResult, MCPError> {
+        let req = ClientRequest::GetPrompt(GetPromptRequest::new(GetPromptParams {
+            name: name.to_string(),
+            arguments: Some(arguments.into_iter().collect()),
+        }));
+        self.request(req, "GetPromptResult")
+    }
+
+    /// Ask for suggestions for `argument` of a prompt or resource template.
+    pub fn complete(&mut self, reference: Reference, argument: ArgumentInfo) -> Result<CompleteResult, MCPError> {
+        let req = ClientRequest::Complete(CompleteRequest::new(CompleteParams {
+            ref_: reference,
+            argument,
+        }));
+        self.request(req, "CompleteResult")
+    }
+
+    pub fn subscribe_resource(&mut self, uri: &str) -> Result<(), MCPError> {
+        let req = ClientRequest::Subscribe(SubscribeRequest::new(SubscribeParams {
+            uri: uri.to_string(),
+        }));
+        self.request::<Value>(req, "EmptyResult").map(|_| ())
+    }
+
+    pub fn unsubscribe_resource(&mut self, uri: &str) -> Result<(), MCPError> {
+        let req = ClientRequest::Unsubscribe(UnsubscribeRequest::new(UnsubscribeParams {
+            uri: uri.to_string(),
+        }));
+        self.request::<Value>(req, "EmptyResult").map(|_| ())
+    }
+
+    /// Send a request and wait for its result, `what` names the result in errors.
+    fn request<R: serde::de::DeserializeOwned>(&mut self, req: ClientRequest, what: &str) -> Result<R, MCPError> {
+        let request_id = self.next_request_id();
+        let req = build_client_request(request_id.clone(), req);
+        let payload = rioc::PayLoad {
+            data: mcp_json_param(&req),
+            ctx: None,
+        };
+
+        let _ = self.handle_outbound(Some(payload));
+
+        let response = self.recieve_response(&request_id)?;
+        match response {
+            JSONRPCMessage::Response(resp) => {
+                serde_json::from_value::<R>(resp.result)
+                    .map_err(|e| MCPError::Protocol(format!("Failed to parse {}: {:?}", what, e)))
+            }
+            JSONRPCMessage::Error(error) => Err(MCPError::Protocol(format!("Error: {:?}", error))),
+            _ => Err(MCPError::Protocol("Invalid response".to_string())),
+        }
+    }
+
     pub fn call_tool(&mut self, params: CallToolParams) -> Result<CallToolResult, MCPError> {
         let call_tool_req = CallToolRequest::new(params);
 
@@ -307,10 +527,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         let _ = self.handle_outbound(Some(payload));
 
         //wait for response
-        let response = self.recieve_with_timeout()?;
+        let response = self.recieve_response(&request_id)?;
         match response {
             JSONRPCMessage::Response(resp) => {
-                assert_eq!(resp.id, request_id);
                 let result = resp.result;
                 serde_json::from_value(result.clone()).map_err(MCPError::Serialization)
             }
@@ -336,10 +555,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         let _ = self.handle_outbound(Some(payload));
 
         //wait for response
-        let response = self.recieve_with_timeout()?;
+        let response = self.recieve_response(&request_id)?;
         match response {
-            JSONRPCMessage::Response(resp) => {
-                assert_eq!(resp.id, request_id);
+            JSONRPCMessage::Response(_) => {
                 Ok(())
             }
             JSONRPCMessage::Error(error) => Err(MCPError::Protocol(format!("Error: {:?}", error))),
@@ -362,10 +580,9 @@ impl <T: ClientProvider + Default + Clone + Send + 'static> Client<T> {
         let _ = self.handle_outbound(Some(payload));
 
         //wait for response
-        let response = self.recieve_with_timeout()?;
+        let response = self.recieve_response(&request_id)?;
         match response {
-            JSONRPCMessage::Response(resp) => {
-                assert_eq!(resp.id, request_id);
+            JSONRPCMessage::Response(_) => {
                 Ok(())
             }
             JSONRPCMessage::Error(error) => Err(MCPError::Protocol(format!("Error: {:?}", error))),
@@ -486,13 +703,19 @@ mod tests {
     use crate::{
         executor::{ClientExecutor, ServerExecutor},
         init_log,
-        schema::schema::{Tool, ToolInputSchema},
+        schema::schema::{
+            BlobResourceContents, Prompt, PromptArgument, PromptMessage, PromptMessageContent,
+            MessageContent, ProgressParams, ProgressToken, PromptReference, RequestMeta, Resource, ResourceReference, SamplingMessage, ToolResultContent, ResourceContents, ResourceTemplate, Role, TextContent,
+            TextResourceContents, Tool, ToolInputSchema,
+        },
         server::{Server, ServerConfig},
         support::definition::McpLayer,
-        transport::{stdio, trace},
+        transport::{loopback::LoopbackTransport, trace},
     };
     use crate::schema::schema::LoggingMessageParams;
     use crate::support::logging::{setup_logging};
+    use once_cell::sync::Lazy;
+    use std::sync::atomic::AtomicUsize;
 
     #[derive(Clone, Default)]
     pub struct TestClientService;
@@ -523,16 +746,83 @@ mod tests {
     }
 
     use super::*;
+
+    /// A server and a client talking over a loopback pair, both executors
+    /// stop when it goes out of scope.
+    struct Connection<T: Default + ClientProvider + Clone + Send + 'static> {
+        client: Client<T>,
+        //clone of the served instance, for calls from outside a request
+        server: Server,
+        client_executor: ClientExecutor,
+        server_executor: ServerExecutor,
+    }
+
+    impl<T: Default + ClientProvider + Clone + Send + 'static> Drop for Connection<T> {
+        fn drop(&mut self) {
+            self.client_executor.stop();
+            self.server_executor.stop();
+        }
+    }
+
+    /// Serve `config` with whatever `register` adds and start a client on the
+    /// other end of a loopback pair, not initialized yet.
+    fn connect<T: Default + ClientProvider + Clone + Send + 'static>(
+        config: ServerConfig,
+        session_id: &str,
+        register: impl FnOnce(&mut Server),
+    ) -> Connection<T> {
+        connect_with(Client::new(), config, session_id, register)
+    }
+
+    /// Like `connect`, for a client configured before its executor copies it.
+    fn connect_with<T: Default + ClientProvider + Clone + Send + 'static>(
+        mut client: Client<T>,
+        config: ServerConfig,
+        session_id: &str,
+        register: impl FnOnce(&mut Server),
+    ) -> Connection<T> {
+        init_log();
+
+        let mut server = Server::new(config);
+        register(&mut server);
+
+        let (server_transport, client_transport) = LoopbackTransport::pair();
+        server.add_transport_layer(server_transport.with_session_id(session_id).create());
+        server.start().unwrap();
+        server.build();
+        let served = server.clone();
+
+        let mut server_executor = ServerExecutor::new();
+        let _ = server_executor.start(server);
+
+        client.with_timeout(Duration::from_secs(2));
+        client.add_transport_layer(client_transport.create());
+        client.start().unwrap();
+        client.build();
+
+        let mut client_executor = ClientExecutor::new();
+        let _ = client_executor.start(client.clone());
+
+        Connection {
+            client,
+            server: served,
+            client_executor,
+            server_executor,
+        }
+    }
+
     #[test]
     fn test_next_request_id() {
         let mut client = Client::<TestClientService>::new();
-        let layer0 = stdio::StdioTransport::new("abc", true).create();
+        let (_server, transport) = LoopbackTransport::pair();
+        transport.close();
 
-        client.add_transport_layer(layer0);
+        client.add_transport_layer(transport.create());
         client.build();
 
         let d = client.handle_inbound();
         println!("{:?}", d);
+        assert!(d.is_err());
     }
 
     #[test]
@@ -562,8 +852,8 @@ mod tests {
             }))
         });
 
-        let server_transport = stdio::StdioTransport::new("abc", true).create();
-        server.add_transport_layer(server_transport);
+        let (server_transport, client_transport) = LoopbackTransport::pair();
+        server.add_transport_layer(server_transport.create());
         let _ = server.start();
         server.build();
 
@@ -574,8 +864,7 @@ mod tests {
         //init client
         let mut client = Client::<TestClientService>::new();
         let client = client.with_timeout(Duration::from_secs(2));
-        let layer0 = stdio::StdioTransport::new("abc", false).create();
-        client.add_transport_layer(layer0);
+        client.add_transport_layer(client_transport.create());
 
         //for debugging
         client.add_protocol_layer(trace::Tracer::new().create());
@@ -616,6 +905,7 @@ mod tests {
         let toolcall_result = client.call_tool(CallToolParams {
             name: "test_tool".to_string(),
             arguments: None,
+            _meta: None,
         });
         println!("Tools/call {:?}", toolcall_result);
         let _= client.cancel();
@@ -627,6 +917,767 @@ mod tests {
         let _= server_executor.stop();
     }
     
+    #[test]
+    fn test_resources() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_resource(Resource {
+                uri: "file:///readme.txt".to_string(),
+                name: "readme".to_string(),
+                description: None,
+                mime_type: Some("text/plain".to_string()),
+                size: None,
+                annotations: None,
+            })
+            .with_resource_template(ResourceTemplate {
+                uri_template: "blob://{bucket}/{+key}".to_string(),
+                name: "blobs".to_string(),
+                description: None,
+                mime_type: Some("application/octet-stream".to_string()),
+                annotations: None,
+            });
+
+        let mut connection = connect::<TestClientService>(config, "resources", |server| {
+            server.register_resource_handler("file:///readme.txt".to_string(), |uri| {
+                Ok(vec![ResourceContents::Text(TextResourceContents {
+                    uri,
+                    mime_type: Some("text/plain".to_string()),
+                    text: "hello resources".to_string(),
+                })])
+            }).unwrap();
+            server.register_resource_template_handler("blob://{bucket}/{+key}".to_string(), |uri, variables| {
+                assert_eq!(variables.get("bucket").unwrap(), "images");
+                assert_eq!(variables.get("key").unwrap(), "2024/cat.png");
+                Ok(vec![ResourceContents::Blob(BlobResourceContents {
+                    uri,
+                    mime_type: None,
+                    blob: "aGVsbG8=".to_string(),
+                })])
+            }).unwrap();
+            assert!(server.register_resource_handler("file:///missing".to_string(), |_| Ok(vec![])).is_err());
+        });
+        let client = &mut connection.client;
+
+        let init_result = client.initialize().unwrap();
+        assert!(init_result["capabilities"].get("resources").is_some());
+
+        let resources = client.list_resources(None).unwrap();
+        assert_eq!(resources.resources.len(), 1);
+        assert_eq!(resources.resources[0].mime_type.as_deref(), Some("text/plain"));
+
+        let templates = client.list_resource_templates(None).unwrap();
+        assert_eq!(templates.resource_templates[0].uri_template, "blob://{bucket}/{+key}");
+
+        let read = client.read_resource("file:///readme.txt").unwrap();
+        match &read.contents[0] {
+            ResourceContents::Text(text) => assert_eq!(text.text, "hello resources"),
+            other => panic!("unexpected contents {:?}", other),
+        }
+
+        let read = client.read_resource("blob://images/2024/cat.png").unwrap();
+        match &read.contents[0] {
+            ResourceContents::Blob(blob) => assert_eq!(blob.uri, "blob://images/2024/cat.png"),
+            other => panic!("unexpected contents {:?}", other),
+        }
+
+        let missing = client.read_resource("file:///missing").unwrap_err();
+        assert!(missing.to_string().contains("-32002"));
+    }
+
+    #[test]
+    fn test_prompts() {
+        let mut config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_prompts_page_size(2);
+        for name in ["greeting", "summary", "review"] {
+            config = config.with_prompt(Prompt {
+                name: name.to_string(),
+                description: None,
+                arguments: Some(vec![
+                    PromptArgument {
+                        name: "topic".to_string(),
+                        description: None,
+                        required: Some(true),
+                    },
+                    PromptArgument {
+                        name: "tone".to_string(),
+                        description: None,
+                        required: Some(false),
+                    },
+                ]),
+            });
+        }
+
+        let mut connection = connect::<TestClientService>(config, "prompts", |server| {
+            server.register_prompt_handler("review".to_string(), |arguments| {
+                let tone = arguments.get("tone").map(String::as_str).unwrap_or("neutral");
+                Ok(GetPromptResult {
+                    description: Some("Review a topic".to_string()),
+                    messages: vec![PromptMessage {
+                        role: Role::User,
+                        content: PromptMessageContent::Text(TextContent {
+                            r#type: "text".to_string(),
+                            text: format!("Review {} in a {} tone", arguments["topic"], tone),
+                            annotations: None,
+                        }),
+                    }],
+                })
+            }).unwrap();
+            assert!(server.register_prompt_handler("unknown".to_string(), |_| unreachable!()).is_err());
+        });
+        let client = &mut connection.client;
+
+        let init_result = client.initialize().unwrap();
+        assert!(init_result["capabilities"].get("prompts").is_some());
+
+        let first = client.list_prompts(None).unwrap();
+        assert_eq!(first.prompts.len(), 2);
+        let second = client.list_prompts(first.next_cursor.clone()).unwrap();
+        assert_eq!(second.prompts[0].name, "review");
+        assert!(second.next_cursor.is_none());
+        assert!(client.list_prompts(Some("bogus".to_string())).is_err());
+
+        let arguments = HashMap::from([("topic".to_string(), "rust".to_string())]);
+        let prompt = client.get_prompt("review", arguments).unwrap();
+        match &prompt.messages[0].content {
+            PromptMessageContent::Text(text) => assert_eq!(text.text, "Review rust in a neutral tone"),
+            other => panic!("unexpected content {:?}", other),
+        }
+
+        let missing = client.get_prompt("review", HashMap::new()).unwrap_err();
+        assert!(missing.to_string().contains("-32602"));
+        assert!(missing.to_string().contains("topic"));
+        assert!(client.get_prompt("unknown", HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_completion() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_prompt(Prompt {
+                name: "translate".to_string(),
+                description: None,
+                arguments: Some(vec![PromptArgument {
+                    name: "language".to_string(),
+                    description: None,
+                    required: Some(true),
+                }]),
+            })
+            .with_resource_template(ResourceTemplate {
+                uri_template: "db://{table}/{id}".to_string(),
+                name: "rows".to_string(),
+                description: None,
+                mime_type: None,
+                annotations: None,
+            });
+
+        let mut connection = connect::<TestClientService>(config, "completion", |server| {
+            server.register_prompt_completion("translate".to_string(), "language".to_string(), |value| {
+                Ok(["english", "esperanto", "french"].iter()
+                    .filter(|language| language.starts_with(&value))
+                    .map(|language| language.to_string())
+                    .collect())
+            }).unwrap();
+            server.register_resource_completion("db://{table}/{id}".to_string(), "id".to_string(), |_| {
+                Ok((0..150).map(|id| id.to_string()).collect())
+            }).unwrap();
+            assert!(server.register_resource_completion("db://{table}/{id}".to_string(), "row".to_string(), |_| Ok(vec![])).is_err());
+            assert!(server.register_prompt_completion("translate".to_string(), "tone".to_string(), |_| Ok(vec![])).is_err());
+        });
+        let client = &mut connection.client;
+
+        let init_result = client.initialize().unwrap();
+        assert!(init_result["capabilities"].get("completions").is_some());
+
+        let argument = |name: &str, value: &str| ArgumentInfo {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        let result = client.complete(Reference::Prompt(PromptReference::new("translate")), argument("language", "e")).unwrap();
+        assert_eq!(result.completion.values, vec!["english", "esperanto"]);
+        assert_eq!(result.completion.has_more, Some(false));
+
+        let result = client.complete(Reference::Resource(ResourceReference::new("db://{table}/{id}")), argument("id", "")).unwrap();
+        assert_eq!(result.completion.values.len(), 100);
+        assert_eq!(result.completion.total, Some(150));
+        assert_eq!(result.completion.has_more, Some(true));
+
+        //no provider for this variable
+        let result = client.complete(Reference::Resource(ResourceReference::new("db://{table}/{id}")), argument("table", "")).unwrap();
+        assert!(result.completion.values.is_empty());
+
+        assert!(client.complete(Reference::Prompt(PromptReference::new("missing")), argument("language", "")).is_err());
+    }
+
+    #[derive(Clone, Default)]
+    pub struct SamplingClientService;
+
+    impl ClientProvider for SamplingClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_create_message(&self, params: CreateMessageParams) -> Result<CreateMessageResult, MCPError> {
+            let prompt = match &params.messages[0].content {
+                MessageContent::Text(text) => text.text.clone(),
+                _ => String::new(),
+            };
+            Ok(CreateMessageResult {
+                role: Role::Assistant,
+                content: MessageContent::Text(TextContent {
+                    r#type: "text".to_string(),
+                    text: format!("sampled: {}", prompt),
+                    annotations: None,
+                }),
+                model: "test-model".to_string(),
+                stop_reason: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_create_message() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_tools(Tool {
+                name: "ask_llm".to_string(),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: None,
+                    required: None,
+                },
+                description: None,
+            });
+
+        let mut client = Client::<SamplingClientService>::new();
+        client.with_sampling(true);
+        let mut connection = connect_with(client, config, "sampling", |server| {
+            server.with_timeout(Duration::from_secs(2));
+            let sampler = server.clone();
+            server.register_tool_handler("ask_llm".to_string(), move |_input, sender, _receiver| {
+                let result = sampler.create_message(CreateMessageParams {
+                    messages: vec![SamplingMessage {
+                        role: Role::User,
+                        content: MessageContent::Text(TextContent {
+                            r#type: "text".to_string(),
+                            text: "hello".to_string(),
+                            annotations: None,
+                        }),
+                    }],
+                    model_preferences: None,
+                    system_prompt: None,
+                    include_context: None,
+                    temperature: None,
+                    max_tokens: 16,
+                    stop_sequences: None,
+                    metadata: None,
+                });
+                let text = match result {
+                    Ok(CreateMessageResult { content: MessageContent::Text(text), .. }) => text.text,
+                    other => format!("failed: {:?}", other),
+                };
+                let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Text, text)));
+                Ok(Value::Null)
+            }).unwrap();
+        });
+        let client = &mut connection.client;
+
+        client.initialize().unwrap();
+        let result = client.call_tool(CallToolParams {
+            name: "ask_llm".to_string(),
+            arguments: None,
+            _meta: None,
+        }).unwrap();
+        match &result.content[0] {
+            ToolResultContent::Text(text) => assert_eq!(text.text, "sampled: hello"),
+            other => panic!("unexpected content {:?}", other),
+        }
+
+        //no session that advertised sampling outside of a request
+        let params = serde_json::from_value::<CreateMessageParams>(serde_json::json!({
+            "messages": [],
+            "maxTokens": 16,
+        })).unwrap();
+        assert!(matches!(connection.server.create_message(params), Err(MCPError::UnsupportedFeature(_))));
+    }
+
+    static ROOTS_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone, Default)]
+    pub struct RootsClientService;
+
+    impl ClientProvider for RootsClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            ROOTS_REQUESTS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_roots() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_tools(Tool {
+                name: "show_roots".to_string(),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: None,
+                    required: None,
+                },
+                description: None,
+            });
+
+        let mut connection = connect::<RootsClientService>(config, "roots", |server| {
+            server.with_timeout(Duration::from_secs(2));
+            let roots_reader = server.clone();
+            server.register_tool_handler("show_roots".to_string(), move |_input, sender, _receiver| {
+                let text = match roots_reader.list_roots() {
+                    Ok(result) => result.roots.iter().map(|root| root.uri.as_str()).collect::<Vec<_>>().join(","),
+                    Err(e) => format!("failed: {}", e),
+                };
+                let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Text, text)));
+                Ok(Value::Null)
+            }).unwrap();
+        });
+        let client = &mut connection.client;
+        let root = |uri: &str| Root {
+            uri: uri.to_string(),
+            name: None,
+        };
+        client.set_roots(vec![root("file:///workspace")]).unwrap();
+
+        client.initialize().unwrap();
+        let show_roots = |client: &mut Client<RootsClientService>| {
+            let result = client.call_tool(CallToolParams {
+                name: "show_roots".to_string(),
+                arguments: None,
+                _meta: None,
+            }).unwrap();
+            match &result.content[0] {
+                ToolResultContent::Text(text) => text.text.clone(),
+                other => panic!("unexpected content {:?}", other),
+            }
+        };
+
+        assert_eq!(show_roots(client), "file:///workspace");
+        assert_eq!(show_roots(client), "file:///workspace");
+        assert_eq!(ROOTS_REQUESTS.load(Ordering::SeqCst), 1);
+
+        //the server refreshes on its own after list_changed
+        client.set_roots(vec![root("file:///workspace"), root("file:///notes")]).unwrap();
+        let start = std::time::Instant::now();
+        while ROOTS_REQUESTS.load(Ordering::SeqCst) < 2 && start.elapsed() < Duration::from_secs(2) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(ROOTS_REQUESTS.load(Ordering::SeqCst), 2);
+        assert_eq!(show_roots(client), "file:///workspace,file:///notes");
+        let requests = ROOTS_REQUESTS.load(Ordering::SeqCst);
+        assert_eq!(show_roots(client), "file:///workspace,file:///notes");
+        assert_eq!(ROOTS_REQUESTS.load(Ordering::SeqCst), requests);
+    }
+
+    static RESOURCE_UPDATES: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    #[derive(Clone, Default)]
+    pub struct SubscribingClientService;
+
+    impl ClientProvider for SubscribingClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_resource_updated(&self, params: Option<Value>) -> Result<(), MCPError> {
+            let uri = params.unwrap()["uri"].as_str().unwrap().to_string();
+            RESOURCE_UPDATES.lock().unwrap().push(uri);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_resource_subscriptions() {
+        let readme = "file:///subscribed.txt";
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_resource_subscriptions(true)
+            .with_resource(Resource {
+                uri: readme.to_string(),
+                name: "subscribed".to_string(),
+                description: None,
+                mime_type: None,
+                size: None,
+                annotations: None,
+            });
+
+        let mut connection = connect::<SubscribingClientService>(config, "resource-subscriptions", |_| {});
+        let notifier = connection.server.clone();
+        let client = &mut connection.client;
+
+        let init_result = client.initialize().unwrap();
+        assert_eq!(init_result["capabilities"]["resources"]["subscribe"], true);
+
+        assert!(client.subscribe_resource("file:///unknown.txt").is_err());
+        client.subscribe_resource(readme).unwrap();
+
+        let wait_for_updates = |count: usize| {
+            let start = std::time::Instant::now();
+            while RESOURCE_UPDATES.lock().unwrap().len() < count && start.elapsed() < Duration::from_secs(2) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            RESOURCE_UPDATES.lock().unwrap().clone()
+        };
+
+        notifier.notify_resource_updated("file:///other.txt").unwrap();
+        notifier.notify_resource_updated(readme).unwrap();
+        assert_eq!(wait_for_updates(1), vec![readme.to_string()]);
+
+        client.unsubscribe_resource(readme).unwrap();
+        notifier.notify_resource_updated(readme).unwrap();
+        //a ping round trip makes sure nothing else was queued before it
+        let _ = client.ping();
+        assert_eq!(RESOURCE_UPDATES.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stale_response() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0");
+
+        let mut connection = connect::<TestClientService>(config, "stale-response", |_| {});
+        let client = &mut connection.client;
+
+        client.initialize().unwrap();
+
+        //answers to requests that already gave up, e.g. after a timeout
+        for stale in [
+            serde_json::json!({"jsonrpc": "2.0", "id": 999, "result": {}}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 998, "error": {"code": -32603, "message": "late"}}),
+        ] {
+            client.cached_response(serde_json::from_value(stale).unwrap()).unwrap();
+        }
+        assert!(client.list_resources(None).unwrap().resources.is_empty());
+        client.ping().unwrap();
+    }
+
+    #[test]
+    fn test_tool_output() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_tools(Tool {
+                name: "describe".to_string(),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: None,
+                    required: None,
+                },
+                description: None,
+            });
+
+        let mut connection = connect::<TestClientService>(config, "tool-output", |server| {
+            server.register_tool_handler("describe".to_string(), move |_input, sender, _receiver| {
+                let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Text, "a picture".to_string())));
+                let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Image, "aGVsbG8=".to_string())));
+                Ok(serde_json::json!({"width": 1}))
+            }).unwrap();
+        });
+        let client = &mut connection.client;
+
+        client.initialize().unwrap();
+        let result = client.call_tool(CallToolParams {
+            name: "describe".to_string(),
+            arguments: None,
+            _meta: None,
+        }).unwrap();
+
+        //all parts arrive in one result, the returned value last
+        assert_eq!(result.content.len(), 3);
+        match (&result.content[0], &result.content[1], &result.content[2]) {
+            (ToolResultContent::Text(caption), ToolResultContent::Image(image), ToolResultContent::Text(value)) => {
+                assert_eq!(caption.text, "a picture");
+                assert_eq!(image.data, "aGVsbG8=");
+                assert_eq!(value.text, r#"{"width":1}"#);
+            }
+            other => panic!("unexpected content {:?}", other),
+        }
+        //no second response for the same call is left behind
+        assert!(client.ping().is_ok());
+    }
+
+    #[test]
+    fn test_tool_errors() {
+        let tool = |name: &str| Tool {
+            name: name.to_string(),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: None,
+                required: None,
+            },
+            description: None,
+        };
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_tools(tool("fail"))
+            .with_tools(tool("boom"))
+            .with_tools(tool("unregistered"));
+
+        let mut connection = connect::<TestClientService>(config, "tool-errors", |server| {
+            server.register_tool_handler("fail".to_string(), move |_input, sender, _receiver| {
+                let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Text, "partial".to_string())));
+                Err(MCPError::Protocol("disk full".to_string()))
+            }).unwrap();
+            server.register_tool_handler("boom".to_string(), move |_input, _sender, _receiver| {
+                panic!("boom");
+            }).unwrap();
+        });
+        //the panic hook may print a backtrace before the job manager sees the panic
+        let client = connection.client.with_timeout(Duration::from_secs(10));
+
+        client.initialize().unwrap();
+        let mut call = |name: &str| client.call_tool(CallToolParams {
+            name: name.to_string(),
+            arguments: None,
+            _meta: None,
+        });
+        let texts = |result: &CallToolResult| result.content.iter().map(|content| match content {
+            ToolResultContent::Text(text) => text.text.clone(),
+            other => panic!("unexpected content {:?}", other),
+        }).collect::<Vec<_>>();
+
+        let failed = call("fail").unwrap();
+        assert_eq!(failed.is_error, Some(true));
+        assert_eq!(texts(&failed), vec!["partial".to_string(), "Protocol error: disk full".to_string()]);
+
+        let panicked = call("boom").unwrap();
+        assert_eq!(panicked.is_error, Some(true));
+        assert_eq!(texts(&panicked), vec!["Tool handler panicked".to_string()]);
+
+        //protocol level failures stay JSON-RPC errors
+        let unknown = call("missing").unwrap_err();
+        assert!(unknown.to_string().contains("-32602"), "{}", unknown);
+        let unregistered = call("unregistered").unwrap_err();
+        assert!(unregistered.to_string().contains("-32603"), "{}", unregistered);
+    }
+
+    crate::tool_input! {
+        pub struct AddArgs {
+            /// First operand
+            pub a: i64,
+            /// Second operand, zero when left out
+            pub b: Option<i64>,
+        }
+    }
+
+    #[test]
+    fn test_typed_tool() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0");
+
+        let mut connection = connect::<TestClientService>(config, "typed-tool", |server| {
+            server.register_typed_tool("add", Some("Add two numbers"), |args: AddArgs, _sender, _receiver| {
+                Ok(Value::String((args.a + args.b.unwrap_or(0)).to_string()))
+            }).unwrap();
+            assert!(server.register_typed_tool("add", None, |_args: AddArgs, _sender, _receiver| Ok(Value::Null)).is_err());
+        });
+        let client = &mut connection.client;
+
+        client.initialize().unwrap();
+        let tools = client.list_tool(None).unwrap();
+        let add = tools.tools.iter().find(|tool| tool.name == "add").unwrap();
+        assert_eq!(add.description.as_deref(), Some("Add two numbers"));
+        assert_eq!(add.input_schema.required, Some(vec!["a".to_string()]));
+        assert_eq!(add.input_schema.properties.as_ref().unwrap().get("a").unwrap()["description"], "First operand");
+
+        let mut call = |arguments: Value| client.call_tool(CallToolParams {
+            name: "add".to_string(),
+            arguments: serde_json::from_value(arguments).unwrap(),
+            _meta: None,
+        });
+        let text = |result: &CallToolResult| match &result.content[0] {
+            ToolResultContent::Text(text) => text.text.clone(),
+            other => panic!("unexpected content {:?}", other),
+        };
+
+        let sum = call(serde_json::json!({"a": 2, "b": 3})).unwrap();
+        assert_eq!((sum.is_error, text(&sum)), (Some(false), "5".to_string()));
+        let sum = call(serde_json::json!({"a": 2})).unwrap();
+        assert_eq!(text(&sum), "2");
+
+        //arguments not matching the schema never reach the handler
+        let invalid = call(serde_json::json!({"a": "two", "b": 1.5})).unwrap_err().to_string();
+        assert!(invalid.contains("-32602"), "{}", invalid);
+        assert!(invalid.contains("/a: expected integer, got string"), "{}", invalid);
+        assert!(invalid.contains("/b: expected integer, got number"), "{}", invalid);
+        let missing = call(serde_json::json!({})).unwrap_err().to_string();
+        assert!(missing.contains("/a: is required"), "{}", missing);
+    }
+
+    static PROGRESS_UPDATES: Lazy<Mutex<Vec<ProgressParams>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    #[derive(Clone, Default)]
+    pub struct ProgressClientService;
+
+    impl ClientProvider for ProgressClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_progress(&self, params: Option<Value>) -> Result<(), MCPError> {
+            let params = serde_json::from_value::<ProgressParams>(params.unwrap()).unwrap();
+            PROGRESS_UPDATES.lock().unwrap().push(params);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        let config = ServerConfig::new()
+            .with_name("MCP Server")
+            .with_version("1.0.0")
+            .with_tools(Tool {
+                name: "count".to_string(),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: None,
+                    required: None,
+                },
+                description: None,
+            })
+            .with_tools(Tool {
+                name: "count-open".to_string(),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: None,
+                    required: None,
+                },
+                description: None,
+            });
+
+        let mut connection = connect::<ProgressClientService>(config, "progress", |server| {
+            for (name, total) in [("count", Some(50.0)), ("count-open", None)] {
+                let reporter = server.clone();
+                server.register_tool_handler(name.to_string(), move |_input, sender, _receiver| {
+                    let mut progress = reporter.progress_reporter();
+                    for step in 1..=50 {
+                        progress.report(step as f64, total, Some(&format!("step {}", step)))?;
+                    }
+                    let _ = sender.send(TaskEvent::Data((crate::schema::schema::LoadType::Text, "counted".to_string())));
+                    Ok(Value::Null)
+                }).unwrap();
+            }
+        });
+        let client = &mut connection.client;
+
+        client.initialize().unwrap();
+        client.call_tool(CallToolParams {
+            name: "count".to_string(),
+            arguments: None,
+            _meta: Some(RequestMeta {
+                progress_token: Some(ProgressToken::String("count-1".to_string())),
+            }),
+        }).unwrap();
+
+        let start = std::time::Instant::now();
+        while PROGRESS_UPDATES.lock().unwrap().last().map(|update| update.progress) != Some(50.0)
+            && start.elapsed() < Duration::from_secs(2) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let updates = PROGRESS_UPDATES.lock().unwrap().clone();
+        //the first update goes out at once, the ones right after it are throttled
+        assert!(updates.len() >= 2 && updates.len() < 50, "{} updates", updates.len());
+        assert_eq!(updates[0].progress, 1.0);
+        assert_eq!(updates[0].message.as_deref(), Some("step 1"));
+        let last = updates.last().unwrap();
+        assert_eq!(last.progress_token, ProgressToken::String("count-1".to_string()));
+        assert_eq!((last.progress, last.total), (50.0, Some(50.0)));
+
+        //no token, no notifications
+        client.call_tool(CallToolParams {
+            name: "count".to_string(),
+            arguments: None,
+            _meta: None,
+        }).unwrap();
+        let _ = client.ping();
+        assert_eq!(PROGRESS_UPDATES.lock().unwrap().len(), updates.len());
+
+        //without a total the last, throttled update is sent when the handler is done
+        PROGRESS_UPDATES.lock().unwrap().clear();
+        client.call_tool(CallToolParams {
+            name: "count-open".to_string(),
+            arguments: None,
+            _meta: Some(RequestMeta {
+                progress_token: Some(ProgressToken::String("count-2".to_string())),
+            }),
+        }).unwrap();
+        let start = std::time::Instant::now();
+        while PROGRESS_UPDATES.lock().unwrap().last().map(|update| update.progress) != Some(50.0)
+            && start.elapsed() < Duration::from_secs(2) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let updates = PROGRESS_UPDATES.lock().unwrap().clone();
+        assert!(updates.len() >= 2 && updates.len() < 50, "{} updates", updates.len());
+        let last = updates.last().unwrap();
+        assert_eq!((last.progress, last.total), (50.0, None));
+        assert_eq!(last.message.as_deref(), Some("step 50"));
+    }
+
     #[test]
     pub fn test_setup_logging(){
         init_log();
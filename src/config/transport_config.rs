@@ -1,4 +1,3 @@
-
 ///
 /// [transport]
 /// type = "tcp"
@@ -6,11 +5,121 @@
 /// address = "127.0.0.1"
 ///
 
-#[derive(Debug,serde::Deserialize)]
+#[derive(Debug,Clone,serde::Deserialize)]
 pub struct HttpTransportConfig {
+    #[serde(rename = "type", default)]
+    pub transport_type: TransportType,
     pub port : u16,
+    #[serde(alias = "address")]
     pub ip_address : String,
     pub enable_tls: bool,
     pub cert_file: Option<String>,
     pub key_file: Option<String>,
-}
\ No newline at end of file
+    /// Path of the single MCP endpoint, e.g. "/mcp".
+    #[serde(default = "default_endpoint")]
+    pub endpoint: String,
+    /// Answer POSTed requests with `application/json` instead of an SSE stream.
+    #[serde(default)]
+    pub json_response: bool,
+    /// Accepted `Origin` header values, all origins are accepted when unset.
+    #[serde(default)]
+    pub allowed_origins: Option<Vec<String>>,
+    /// Events kept per session for `Last-Event-ID` replay.
+    #[serde(default = "default_replay_buffer")]
+    pub replay_buffer: usize,
+    /// Seconds a session may go without requests before it is dropped along
+    /// with its replay buffer, 0 keeps sessions until they are deleted.
+    #[serde(default = "default_session_idle_timeout")]
+    pub session_idle_timeout: u64,
+    /// Requests served at the same time, further ones get a 503.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Also serve the 2024-11-05 HTTP+SSE transport for older clients.
+    #[serde(default)]
+    pub legacy_sse: bool,
+    /// Legacy stream endpoint, announces the messages endpoint on connect.
+    #[serde(default = "default_sse_endpoint")]
+    pub sse_endpoint: String,
+    /// Legacy endpoint receiving client messages.
+    #[serde(default = "default_messages_endpoint")]
+    pub messages_endpoint: String,
+    /// Socket file for the unix transport.
+    #[serde(default)]
+    pub socket_path: Option<String>,
+    /// Message framing for the tcp and unix transports.
+    #[serde(default)]
+    pub framing: Framing,
+}
+
+/// Kind of transport selected by the `type` key.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportType {
+    #[default]
+    Http,
+    Websocket,
+    Tcp,
+    Unix,
+}
+
+/// How messages are delimited on a raw byte stream.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Framing {
+    /// One JSON-RPC message per line.
+    #[default]
+    Newline,
+    /// Each message preceded by its length as a big-endian u32.
+    Length,
+    /// `Content-Length` headers followed by the body, the way LSP frames JSON-RPC.
+    #[serde(rename = "content-length")]
+    ContentLength,
+}
+
+fn default_endpoint() -> String {
+    "/mcp".to_string()
+}
+
+fn default_replay_buffer() -> usize {
+    256
+}
+
+fn default_session_idle_timeout() -> u64 {
+    300
+}
+
+fn default_max_concurrent_requests() -> usize {
+    256
+}
+
+fn default_sse_endpoint() -> String {
+    "/sse".to_string()
+}
+
+fn default_messages_endpoint() -> String {
+    "/messages".to_string()
+}
+
+impl Default for HttpTransportConfig {
+    fn default() -> Self {
+        Self {
+            transport_type: TransportType::Http,
+            port: 8080,
+            ip_address: "127.0.0.1".to_string(),
+            enable_tls: false,
+            cert_file: None,
+            key_file: None,
+            endpoint: default_endpoint(),
+            json_response: false,
+            allowed_origins: None,
+            replay_buffer: default_replay_buffer(),
+            session_idle_timeout: default_session_idle_timeout(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            legacy_sse: false,
+            sse_endpoint: default_sse_endpoint(),
+            messages_endpoint: default_messages_endpoint(),
+            socket_path: None,
+            framing: Framing::Newline,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use disruptor::Producer;
 use log::{info, warn};
 
-use crate::{client::{Client, ClientProvider}, server::Server, support::ControlBus};
+use crate::{client::{Client, ClientProvider}, server::Server, support::ControlBus, MCPError};
 
 
 pub struct ServerExecutor{
@@ -52,7 +52,16 @@ impl ServerExecutor {
                     Err(_) => {}        
                 }
 
-                let _ = server.serve();
+                match server.serve() {
+                    //transport closed, nothing more to serve
+                    Err(MCPError::Closed(e)) => {
+                        warn!("Server transport closed: {}", e);
+                        let _ = server.stop();
+                        break;
+                    }
+                    Err(e) => warn!("Server failed to serve: {}", e),
+                    Ok(_) => {}
+                }
            }
         });
 
@@ -97,7 +106,14 @@ impl ClientExecutor {
                     Err(_) => {}        
                 }
 
-                let _ = client.serve();
+                match client.serve() {
+                    Err(MCPError::Closed(e)) => {
+                        warn!("Client transport closed: {}", e);
+                        break;
+                    }
+                    Err(e) => warn!("Client failed to serve: {}", e),
+                    Ok(_) => {}
+                }
            }
         });
 
@@ -30,6 +30,11 @@ pub fn init_log(){
     let _logger = McpInterceptorLogger::init();
 }
 
+/// Initialize logging for servers speaking MCP over stdin/stdout.
+pub fn init_stderr_log(){
+    McpInterceptorLogger::init_stderr();
+}
+
 
 #[cfg(test)]
 mod tests {
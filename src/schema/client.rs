@@ -15,7 +15,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use super::schema::{CallToolParams, CallToolRequest, CancelledNotification, CancelledParams, ClientNotification, ClientRequest, ClientShutdownRequest, InitializeParams, InitializeRequest, InitializedNotification, InitializedNotificationParams, JSONRPCNotification, JSONRPCRequest, ListToolsRequest, PaginatedParams, PingRequest, RequestId, SetLevelParams, SetLevelRequest};
+use super::schema::{CallToolParams, CallToolRequest, CancelledNotification, CancelledParams, ClientNotification, CompleteParams, CompleteRequest, ClientRequest, ClientShutdownRequest, GetPromptParams, GetPromptRequest, InitializeParams, InitializeRequest, InitializedNotification, InitializedNotificationParams, JSONRPCNotification, JSONRPCRequest, ListPromptsRequest, ListResourceTemplatesRequest, ListResourcesRequest, ListToolsRequest, PaginatedParams, PingRequest, PromptReference, ReadResourceParams, ReadResourceRequest, RequestId, ResourceReference, RootsListChangedNotification, SetLevelParams, SetLevelRequest, SubscribeParams, SubscribeRequest, UnsubscribeParams, UnsubscribeRequest};
 use crate::schema::json_rpc::mcp_param;
 
 impl InitializeRequest {
@@ -40,6 +40,95 @@ impl ListToolsRequest {
 }
 
 
+impl ListResourcesRequest {
+    pub fn new(params :Option<PaginatedParams>) -> Self {
+        Self {
+            method:"resources/list".to_string(),
+            params
+        }
+    }
+}
+
+impl ListResourceTemplatesRequest {
+    pub fn new(params :Option<PaginatedParams>) -> Self {
+        Self {
+            method:"resources/templates/list".to_string(),
+            params
+        }
+    }
+}
+
+impl ReadResourceRequest {
+    pub fn new(params :ReadResourceParams) -> Self {
+        Self {
+            method:"resources/read".to_string(),
+            params
+        }
+    }
+}
+
+impl ListPromptsRequest {
+    pub fn new(params :Option<PaginatedParams>) -> Self {
+        Self {
+            method:"prompts/list".to_string(),
+            params
+        }
+    }
+}
+
+impl GetPromptRequest {
+    pub fn new(params :GetPromptParams) -> Self {
+        Self {
+            method:"prompts/get".to_string(),
+            params
+        }
+    }
+}
+
+impl CompleteRequest {
+    pub fn new(params :CompleteParams) -> Self {
+        Self {
+            method:"completion/complete".to_string(),
+            params
+        }
+    }
+}
+
+impl PromptReference {
+    pub fn new(name: &str) -> Self {
+        Self {
+            r#type: "ref/prompt".to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+impl ResourceReference {
+    pub fn new(uri: &str) -> Self {
+        Self {
+            r#type: "ref/resource".to_string(),
+            uri: uri.to_string(),
+        }
+    }
+}
+
+impl SubscribeRequest {
+    pub fn new(params :SubscribeParams) -> Self {
+        Self {
+            method:"resources/subscribe".to_string(),
+            params
+        }
+    }
+}
+
+impl UnsubscribeRequest {
+    pub fn new(params :UnsubscribeParams) -> Self {
+        Self {
+            method:"resources/unsubscribe".to_string(),
+            params
+        }
+    }
+}
 
 impl InitializedNotification {
     pub fn new(params: InitializedNotificationParams) -> Self {
@@ -50,6 +139,20 @@ impl InitializedNotification {
     }
 }
 
+impl RootsListChangedNotification {
+    pub fn new() -> Self {
+        Self {
+            method: "notifications/roots/list_changed".to_string(),
+        }
+    }
+}
+
+impl Default for RootsListChangedNotification {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CallToolRequest{
     pub fn new(params :CallToolParams) -> Self {
         Self{
@@ -27,6 +27,7 @@ pub mod error_codes {
     pub const METHOD_NOT_FOUND: i32 = -32601;
     pub const INVALID_PARAMS: i32 = -32602;
     pub const INTERNAL_ERROR: i32 = -32603;
+    pub const RESOURCE_NOT_FOUND: i32 = -32002;
 }
 
 
@@ -123,7 +124,8 @@ pub enum ProgressToken {
 }
 
 /// Request metadata
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RequestMeta {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub progress_token: Option<ProgressToken>,
@@ -322,6 +324,10 @@ pub struct ServerCapabilities {
     /// Present if the server offers any tools to call.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<ToolsCapability>,
+
+    /// Present if the server supports argument autocompletion suggestions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completions: Option<Value>,
 }
 
 /// After receiving an initialize request from the client, the server sends this response.
@@ -370,6 +376,7 @@ pub struct ProgressNotification {
 
 /// Parameters for progress notification
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProgressParams {
     /// The progress token which was given in the initial request.
     pub progress_token: ProgressToken,
@@ -380,6 +387,10 @@ pub struct ProgressParams {
     /// Total number of items to process (or total progress required), if known.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total: Option<f64>,
+
+    /// A human readable description of the current progress.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -417,6 +428,7 @@ pub struct PaginatedParams {
 
 /// A known resource that the server is capable of reading.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Resource {
     /// The URI of this resource.
     pub uri: String,
@@ -442,6 +454,7 @@ pub struct Resource {
 
 /// The server's response to a resources/list request from the client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListResourcesResult {
     /// An opaque token representing the pagination position after the last returned result.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -476,19 +489,20 @@ pub struct ReadResourceParams {
 /// The server's response to a resources/read request from the client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadResourceResult {
-    pub contents: Vec<ResourceContent>,
+    pub contents: Vec<ResourceContents>,
 }
 
 
 
 /// Binary resource contents
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BlobResourceContents {
     /// The URI of this resource.
     pub uri: String,
 
     /// The MIME type of this resource, if known.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "mime_type")]
     pub mime_type: Option<String>,
 
     /// A base64-encoded string representing the binary data of the item.
@@ -563,12 +577,13 @@ pub enum ResourceContents {
 
 /// Text resource contents
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextResourceContents {
     /// The URI of this resource.
     pub uri: String,
 
     /// The MIME type of this resource, if known.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "mime_type")]
     pub mime_type: Option<String>,
 
     /// The text of the item.
@@ -615,6 +630,7 @@ pub struct Prompt {
 
 /// The server's response to a prompts/list request from the client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListPromptsResult {
     /// An opaque token representing the pagination position after the last returned result.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -653,9 +669,11 @@ pub struct TextContent {
 
 /// An image provided to or from an LLM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ImageContent {
     pub r#type: String,
     pub data: String,
+    #[serde(alias = "mime_type")]
     pub mime_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub annotations: Option<Annotations>,
@@ -694,7 +712,6 @@ pub enum PromptMessageContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptMessage {
     pub role: Role,
-    #[serde(flatten)]
     pub content: PromptMessageContent,
 }
 
@@ -774,7 +791,7 @@ pub struct Root {
 }
 
 /// Tool result content
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum ToolResultContent {
     Audio(AudioContent),
@@ -783,6 +800,23 @@ pub enum ToolResultContent {
     Resource(EmbeddedResource),
 }
 
+//audio and image parts share a shape, pick the variant by the type field
+impl<'de> Deserialize<'de> for ToolResultContent {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let content = match value.get("type").and_then(Value::as_str) {
+            Some("audio") => serde_json::from_value(value).map(ToolResultContent::Audio),
+            Some("image") => serde_json::from_value(value).map(ToolResultContent::Image),
+            Some("resource") => serde_json::from_value(value).map(ToolResultContent::Resource),
+            _ => serde_json::from_value(value).map(ToolResultContent::Text),
+        };
+        content.map_err(serde::de::Error::custom)
+    }
+}
+
 /// The server's response to a tool call.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallToolResult {
@@ -809,6 +843,10 @@ pub struct CallToolParams {
     /// Arguments for the tool
     #[serde(skip_serializing_if = "Option::is_none")]
     pub arguments: Option<DashMap<String, Value>>,
+
+    /// Request metadata, e.g. the token progress notifications should carry
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub _meta: Option<RequestMeta>,
 }
 
 /// An optional notification from the server to the client, informing it that the list of tools it offers has changed.
@@ -971,7 +1009,6 @@ pub enum MessageContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SamplingMessage {
     pub role: Role,
-    #[serde(flatten)]
     pub content: MessageContent,
 }
 
@@ -986,6 +1023,7 @@ pub enum IncludeContext {
 
 /// Parameters for create message request
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateMessageParams {
     /// The messages to sample from
     pub messages: Vec<SamplingMessage>,
@@ -1040,12 +1078,12 @@ pub enum StopReason {
 
 /// The client's response to a sampling/create_message request from the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateMessageResult {
     /// The role of the message
     pub role: Role,
 
     /// The content of the message
-    #[serde(flatten)]
     pub content: MessageContent,
 
     /// The name of the model that generated the message.
@@ -1113,12 +1151,14 @@ pub struct CompleteParams {
 /// The server's response to a completion/complete request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompleteResult {
-    _meta: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub _meta: Option<HashMap<String, String>>,
     pub completion: CompletionInfo,
 }
 
 /// Completion information
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CompletionInfo {
     /// An array of completion values.
     pub values: Vec<String>,
@@ -1141,6 +1181,7 @@ pub struct ListRootsRequest {
 /// The client's response to a roots/list request from the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListRootsResult {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
     pub _meta: Option<HashMap<String, String>>,
     pub roots: Vec<Root>,
 }
@@ -1154,6 +1195,7 @@ pub struct RootsListChangedNotification {
 
 /// A template description for resources available on the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResourceTemplate {
     /// A URI template (according to RFC 6570) that can be used to construct resource URIs.
     pub uri_template: String,
@@ -1175,6 +1217,7 @@ pub struct ResourceTemplate {
 
 /// The server's response to a resources/templates/list request from the client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListResourceTemplatesResult {
     /// An opaque token representing the pagination position after the last returned result.
     #[serde(skip_serializing_if = "Option::is_none")]
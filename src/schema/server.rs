@@ -17,7 +17,7 @@
 
 use serde_json::Value;
 use crate::schema::schema::{EmptyResult, JSONRPCError};
-use super::{json_rpc::mcp_param, schema::{JSONRPCNotification, JSONRPCRequest, ListRootsRequest, LoggingMessageNotification, LoggingMessageParams, RequestId, ServerNotification, ServerRequest}};
+use super::{json_rpc::mcp_param, schema::{CreateMessageParams, CreateMessageRequest, JSONRPCNotification, JSONRPCRequest, ListRootsRequest, LoggingMessageNotification, LoggingMessageParams, ProgressNotification, ProgressParams, RequestId, ResourceListChangedNotification, ResourceUpdatedNotification, ResourceUpdatedParams, ServerNotification, ServerRequest}};
 
 impl ListRootsRequest {
     pub fn new() -> Self {
@@ -27,6 +27,15 @@ impl ListRootsRequest {
     }
 }
 
+impl CreateMessageRequest {
+    pub fn new(params: CreateMessageParams) -> Self {
+        Self {
+            method: "sampling/createMessage".to_string(),
+            params,
+        }
+    }
+}
+
 impl LoggingMessageNotification{
     pub fn new(params : LoggingMessageParams) -> Self {
         Self {
@@ -36,6 +45,38 @@ impl LoggingMessageNotification{
     }
 }
 
+impl ProgressNotification {
+    pub fn new(params: ProgressParams) -> Self {
+        Self {
+            method: "notifications/progress".to_string(),
+            params,
+        }
+    }
+}
+
+impl ResourceUpdatedNotification {
+    pub fn new(params: ResourceUpdatedParams) -> Self {
+        Self {
+            method: "notifications/resources/updated".to_string(),
+            params,
+        }
+    }
+}
+
+impl ResourceListChangedNotification {
+    pub fn new() -> Self {
+        Self {
+            method: "notifications/resources/list_changed".to_string(),
+        }
+    }
+}
+
+impl Default for ResourceListChangedNotification {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EmptyResult {
     pub fn new() -> Self {
         EmptyResult{
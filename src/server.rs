@@ -2,12 +2,12 @@ use crate::{
     schema::{
         json_rpc::{mcp_from_value, mcp_json_param, mcp_param, mcp_to_value},
         schema::{
-            CallToolParams, EmptyResult, Implementation, InitializeParams, InitializeResult, JSONRPCError, JSONRPCMessage, JSONRPCResponse, ListRootsRequest, ListToolsResult, LoggingLevel, LoggingMessageNotification, LoggingMessageParams, RequestId, ServerCapabilities, ServerNotification, ServerRequest, SetLevelParams, TextContent, Tool, ToolResultContent, ToolsCapability, LATEST_PROTOCOL_VERSION, SESSION_ID_KEY
+            CallToolParams, EmptyResult, Implementation, InitializeParams, InitializeResult, JSONRPCError, JSONRPCMessage, JSONRPCResponse, ClientCapabilities, CompleteParams, CompleteResult, CompletionInfo, CreateMessageParams, CreateMessageRequest, CreateMessageResult, GetPromptParams, GetPromptResult, ListPromptsResult, ListResourceTemplatesResult, ListResourcesResult, Prompt, PromptsCapability, ListRootsRequest, ListRootsResult, ListToolsResult, ReadResourceParams, ReadResourceResult, Resource, ResourceListChangedNotification, ResourceTemplate, ResourceUpdatedNotification, ResourceUpdatedParams, ResourcesCapability, Reference, SubscribeParams, LoggingLevel, LoggingMessageNotification, LoggingMessageParams, ProgressNotification, ProgressParams, ProgressToken, RequestId, ServerCapabilities, ServerNotification, ServerRequest, SetLevelParams, TextContent, Tool, ToolResultContent, ToolsCapability, LATEST_PROTOCOL_VERSION, SESSION_ID_KEY
         },
         server::{build_server_notification, build_server_request},
     },
     support::{
-        disruptor::{DisruptorFactory, DisruptorWriter}, jobman::JobManager, logging::setup_logging, sessons::SESSION_STORE, ControlBus
+        disruptor::{DisruptorFactory, DisruptorWriter}, jobman::{FinishedJob, JobManager}, logging::setup_logging, sessons::SESSION_STORE, ControlBus
     },
     MCPError,
 };
@@ -19,19 +19,29 @@ use log::{info};
 use rioc::{ChainContext, JobTask, LayerChain, LayerResult, PayLoad, SharedLayer, TaskEvent};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap, sync::{Arc, Mutex}, time::Duration
+    collections::{HashMap, HashSet}, sync::{atomic::{AtomicI64, Ordering}, Arc, Mutex}, time::{Duration, Instant}
 };
 use std::cell::RefCell;
 use crossbeam::channel::{Receiver, Sender};
 use crate::schema::schema::{AudioContent, CallToolResult, CancelledParams, EmbeddedResource, ImageContent, LoadType, ResourceContents,error_codes};
 use crate::schema::server::build_server_error;
 use crate::support::sessons::{get_current_session, set_session_id, SessionItem};
+use crate::support::json_schema::SchemaValidator;
+use crate::transport::read_inbound;
+use crate::support::tool_input::ToolInput;
+use crate::support::uri_template::UriTemplate;
 
 #[derive(Clone)]
 pub struct ServerConfig {
     pub name: String,
     pub version: String,
     pub tools: Vec<Tool>,
+    pub resources: Vec<Resource>,
+    pub resource_templates: Vec<ResourceTemplate>,
+    pub resource_subscriptions: bool,
+    pub prompts: Vec<Prompt>,
+    /// Prompts returned per prompts/list page, everything at once when unset.
+    pub prompts_page_size: Option<usize>,
     pub timeout: Option<Duration>,
 }
 
@@ -41,6 +51,11 @@ impl ServerConfig {
             name: "MCP Server".to_string(),
             version: "1.0.0".to_string(),
             tools: Vec::new(),
+            resources: Vec::new(),
+            resource_templates: Vec::new(),
+            resource_subscriptions: false,
+            prompts: Vec::new(),
+            prompts_page_size: None,
             timeout: None,
         }
     }
@@ -60,6 +75,32 @@ impl ServerConfig {
         self
     }
 
+    pub fn with_resource(mut self, resource: Resource) -> Self {
+        self.resources.push(resource);
+        self
+    }
+
+    pub fn with_resource_template(mut self, template: ResourceTemplate) -> Self {
+        self.resource_templates.push(template);
+        self
+    }
+
+    /// Accept resources/subscribe and advertise resource update notifications.
+    pub fn with_resource_subscriptions(mut self, enabled: bool) -> Self {
+        self.resource_subscriptions = enabled;
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: Prompt) -> Self {
+        self.prompts.push(prompt);
+        self
+    }
+
+    pub fn with_prompts_page_size(mut self, page_size: usize) -> Self {
+        self.prompts_page_size = Some(page_size.max(1));
+        self
+    }
+
     pub fn with_timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
         self
@@ -86,17 +127,59 @@ pub trait ServerProvider {
 
 pub type ToolHandler = Arc<Box<dyn Fn(Value,Sender<TaskEvent<(LoadType,String),i32>>,Receiver<String>,) -> Result<Value, MCPError> + Send + Sync + 'static>>;
 
+/// Reads a resource given its URI and, for templates, the variables matched in it.
+pub type ResourceHandler = Arc<Box<dyn Fn(String, HashMap<String, String>) -> Result<Vec<ResourceContents>, MCPError> + Send + Sync + 'static>>;
+
+/// How long a server-initiated request waits for the client when no timeout is configured.
+pub const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Session item holding the capabilities the client sent with initialize.
+const CLIENT_CAPABILITIES_KEY: &str = "client_capabilities";
+
+/// Most values a completion/complete response may carry.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+/// Suggests values for an argument given what has been typed so far.
+pub type CompletionHandler = Arc<Box<dyn Fn(String) -> Result<Vec<String>, MCPError> + Send + Sync + 'static>>;
+
+/// Reference type, prompt name or uri template, and argument name.
+type CompletionKey = (String, String, String);
+
+/// Session a server-initiated request went to, and where its response goes.
+type PendingRequest = (String, Mutex<may::sync::mpsc::Sender<JSONRPCMessage>>);
+
+/// Shortest gap between two progress notifications of the same tool call.
+pub const MIN_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+//token the caller of the tool call running on this coroutine asked progress for
+may::coroutine_local!(static PROGRESS_TOKEN: RefCell<Option<ProgressToken>> = RefCell::new(None));
+
+/// Renders a prompt from the arguments supplied with prompts/get.
+pub type PromptHandler = Arc<Box<dyn Fn(HashMap<String, String>) -> Result<GetPromptResult, MCPError> + Send + Sync + 'static>>;
+
 #[derive(Clone)]
 pub struct Server {
     config: ServerConfig,
     tool_handlers: Arc<Mutex<HashMap<String, ToolHandler>>>,
+    //input schema of every tool with a handler, compiled at registration
+    tool_validators: Arc<Mutex<HashMap<String, Arc<SchemaValidator>>>>,
+    resource_handlers: Arc<Mutex<HashMap<String, ResourceHandler>>>,
+    template_handlers: Arc<Mutex<Vec<(UriTemplate, ResourceHandler)>>>,
+    prompt_handlers: Arc<Mutex<HashMap<String, PromptHandler>>>,
+    completion_handlers: Arc<Mutex<HashMap<CompletionKey, CompletionHandler>>>,
+    //initialized sessions and the resource uris each one subscribed to
+    sessions: Arc<DashMap<String, HashSet<String>>>,
     notify: Arc<ControlBus>,
     chain: iBag<LayerChain>,
     disruptor: Option<DisruptorWriter>,
     is_initialized: bool,
-    current_request_id: Option<i64>,
     cached: Arc<Mutex<Vec<JSONRPCMessage>>>,
-    next_request_id: i64,
+    next_request_id: Arc<AtomicI64>,
+    //server-initiated requests waiting for the client, with the session they went to
+    pending_requests: Arc<DashMap<RequestId, PendingRequest>>,
+    //roots each session's client last reported, with a generation bumped on
+    //every list_changed so a slow refresh cannot overwrite a newer one
+    roots: Arc<DashMap<String, (u64, Option<ListRootsResult>)>>,
     timeout_duration: Option<Duration>,
     state: ServerState,
     job_manager: Arc<Mutex<RefCell<JobManager>>>,
@@ -108,12 +191,19 @@ impl Server {
         Self {
             config,
             tool_handlers: Arc::new(Mutex::new(HashMap::new())),
+            tool_validators: Arc::new(Mutex::new(HashMap::new())),
+            resource_handlers: Arc::new(Mutex::new(HashMap::new())),
+            template_handlers: Arc::new(Mutex::new(Vec::new())),
+            prompt_handlers: Arc::new(Mutex::new(HashMap::new())),
+            completion_handlers: Arc::new(Mutex::new(HashMap::new())),
+            sessions: Arc::new(DashMap::new()),
             notify: Arc::new(ControlBus::new()),
             chain: iBag::new(LayerChain::new()),
             disruptor: None,
             is_initialized: false,
-            next_request_id: 0,
-            current_request_id: None,
+            next_request_id: Arc::new(AtomicI64::new(0)),
+            pending_requests: Arc::new(DashMap::new()),
+            roots: Arc::new(DashMap::new()),
             cached: Arc::new(Mutex::new(Vec::new())),
             timeout_duration: None,
             state: ServerState::Uninitialized,
@@ -140,30 +230,40 @@ impl Server {
     }
 
     pub fn serve(&self) -> Result<(), MCPError> {
-        let _ = self.handle_inbound();
-        Ok(())
+        read_inbound(|| self.handle_inbound())
     }
 
-    pub fn list_roots(&mut self) -> Result<Value, MCPError> {
-        let request = ListRootsRequest::new();
+    /// Roots of the client behind the current session, asked for once and
+    /// then served from cache until the client reports a change.
+    pub fn list_roots(&self) -> Result<ListRootsResult, MCPError> {
+        let session_id = get_current_session();
+        if let Some(roots) = self.roots.get(&session_id).and_then(|entry| entry.1.clone()) {
+            return Ok(roots);
+        }
 
-        let req = ServerRequest::ListRootsRequest(request);
-        let request_id = self.next_request_id();
-        let req = build_server_request(request_id, req);
-        let payload = rioc::PayLoad {
-            data: mcp_json_param(&req),
-            ctx: None,
-        };
+        let capabilities = SESSION_STORE.get_session(&session_id)
+            .and_then(|session| session.get_item(CLIENT_CAPABILITIES_KEY))
+            .and_then(|capabilities| serde_json::from_str::<ClientCapabilities>(&capabilities).ok());
+        if capabilities.and_then(|capabilities| capabilities.roots).is_none() {
+            return Err(MCPError::UnsupportedFeature(format!(
+                "Client of session {} does not support roots",
+                session_id
+            )));
+        }
+        self.fetch_roots(&session_id)
+    }
 
-        //send initial request to server
-        let _ = self.handle_outbound(Some(payload));
-        
-        //wait for response
-        let result = self.recieve_with_timeout()?;
-        if let JSONRPCMessage::Response(response) = result {
-            return Ok(response.result);
+    fn fetch_roots(&self, session_id: &str) -> Result<ListRootsResult, MCPError> {
+        let generation = self.roots.get(session_id).map(|entry| entry.0).unwrap_or(0);
+        let result = self.request_client(session_id, ServerRequest::ListRootsRequest(ListRootsRequest::new()))?;
+        let roots: ListRootsResult = serde_json::from_value(result)
+            .map_err(|e| MCPError::Protocol(format!("Failed to parse ListRootsResult: {:?}", e)))?;
+
+        let mut entry = self.roots.entry(session_id.to_string()).or_insert((generation, None));
+        if entry.0 == generation {
+            entry.1 = Some(roots.clone());
         }
-        Err(MCPError::Transport("Failed to get roots list".to_string()))
+        Ok(roots)
     }
 
     pub fn recieve_with_timeout(&mut self) -> Result<JSONRPCMessage, MCPError> {
@@ -200,11 +300,121 @@ impl Server {
         Err(MCPError::Transport("No cached message".to_string()))
     }
 
-    fn next_request_id(&mut self) -> RequestId {
-        self.next_request_id += 1;
-        let id = self.next_request_id;
-        self.current_request_id = Some(id);
-        RequestId::Number(id)
+    fn next_request_id(&self) -> RequestId {
+        RequestId::Number(self.next_request_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    /// Ask the client of the current session to sample an LLM. Meant to be
+    /// called from a tool handler, it blocks until the client answers or the
+    /// request times out.
+    pub fn create_message(&self, params: CreateMessageParams) -> Result<CreateMessageResult, MCPError> {
+        let session_id = get_current_session();
+        let capabilities = SESSION_STORE.get_session(&session_id)
+            .and_then(|session| session.get_item(CLIENT_CAPABILITIES_KEY))
+            .and_then(|capabilities| serde_json::from_str::<ClientCapabilities>(&capabilities).ok());
+        if capabilities.and_then(|capabilities| capabilities.sampling).is_none() {
+            return Err(MCPError::UnsupportedFeature(format!(
+                "Client of session {} does not support sampling",
+                session_id
+            )));
+        }
+
+        let request = ServerRequest::CreateMessageRequest(CreateMessageRequest::new(params));
+        let result = self.request_client(&session_id, request)?;
+        serde_json::from_value(result)
+            .map_err(|e| MCPError::Protocol(format!("Failed to parse CreateMessageResult: {:?}", e)))
+    }
+
+    /// Progress reporter for the tool call running on the current coroutine.
+    /// Reports are dropped when the caller did not ask for progress.
+    pub fn progress_reporter(&self) -> ProgressReporter {
+        ProgressReporter {
+            server: self.clone(),
+            session_id: get_current_session(),
+            token: PROGRESS_TOKEN.with(|token| token.borrow().clone()),
+            last_progress: None,
+            last_sent: None,
+            pending: None,
+        }
+    }
+
+    /// Send a request to the client behind `session_id` and wait for the
+    /// response carrying the same id.
+    fn request_client(&self, session_id: &str, request: ServerRequest) -> Result<Value, MCPError> {
+        let id = self.next_request_id();
+        //may's channel parks a tool handler's coroutine instead of its worker thread
+        let (tx, rx) = may::sync::mpsc::channel();
+        self.pending_requests.insert(id.clone(), (session_id.to_string(), Mutex::new(tx)));
+
+        let req = build_server_request(id.clone(), request);
+        let mut ctx = ChainContext { data: HashMap::new() };
+        ctx.data.insert(SESSION_ID_KEY.to_string(), session_id.to_string());
+        let _ = self.handle_outbound(Some(rioc::PayLoad {
+            data: mcp_json_param(&req),
+            ctx: Some(ctx),
+        }));
+
+        let timeout = self.timeout_duration.or(self.config.timeout).unwrap_or(CLIENT_REQUEST_TIMEOUT);
+        let response = rx.recv_timeout(timeout);
+        self.pending_requests.remove(&id);
+        match response {
+            Ok(JSONRPCMessage::Response(response)) => Ok(response.result),
+            Ok(JSONRPCMessage::Error(error)) => Err(MCPError::Protocol(format!("Error: {:?}", error))),
+            Ok(_) => Err(MCPError::Protocol("Invalid response".to_string())),
+            Err(_) => Err(MCPError::Timeout(format!("No response to request {:?} within {:?}", id, timeout))),
+        }
+    }
+
+    fn session_of(ctx: Option<&ChainContext>) -> String {
+        ctx.and_then(|ctx| ctx.data.get(SESSION_ID_KEY).cloned())
+            .unwrap_or_else(|| "local".to_string())
+    }
+
+    /// Hand a response to the server-initiated request waiting for it, as
+    /// long as it arrived on the session the request was sent to.
+    fn resolve_pending(&self, ctx: Option<&ChainContext>, id: &RequestId, message: JSONRPCMessage) -> Option<JSONRPCMessage> {
+        let session_id = Self::session_of(ctx);
+        match self.pending_requests.remove_if(id, |_, (pending_session, _)| *pending_session == session_id) {
+            Some((_, (_, tx))) => {
+                let _ = tx.lock().unwrap().send(message);
+                None
+            }
+            None => Some(message),
+        }
+    }
+
+    /// Add a tool whose input schema is generated from `A` and whose handler
+    /// gets the arguments already deserialized into `A`. Takes `&mut self`
+    /// because the tool is added to the server config, which is not behind
+    /// a lock, so it has to be done before the server is started.
+    pub fn register_typed_tool<A, F>(&mut self, tool_name: &str, description: Option<&str>, handler: F) -> Result<(), MCPError>
+    where
+        A: ToolInput + 'static,
+        F: Fn(A,Sender<TaskEvent<(LoadType,String),i32>>,Receiver<String>) -> Result<Value, MCPError> + Send  + Sync + 'static,
+    {
+        if self.config.tools.iter().any(|tool| tool.name == tool_name) {
+            return Err(MCPError::Transport(format!(
+                "Tool {} already in server config",
+                tool_name
+            )));
+        }
+        let tool = Tool {
+            name: tool_name.to_string(),
+            description: description.map(|description| description.to_string()),
+            input_schema: A::input_schema(),
+        };
+
+        //only list the tool once it can actually be called
+        let name = tool_name.to_string();
+        self.insert_tool_handler(&tool, move |input, sender, receiver| {
+            //a call without arguments is one with all of them left out
+            let input = if input.is_null() { json!({}) } else { input };
+            let args = serde_json::from_value::<A>(input)
+                .map_err(|e| MCPError::Protocol(format!("Invalid arguments for tool {}: {}", name, e)))?;
+            handler(args, sender, receiver)
+        })?;
+        self.config.tools.push(tool);
+        Ok(())
     }
 
     pub fn register_tool_handler<F>(&self, tool_name: String, handler: F) -> Result<(), MCPError>
@@ -212,12 +422,20 @@ impl Server {
         F: Fn(Value,Sender<TaskEvent<(LoadType,String),i32>>,Receiver<String>) -> Result<Value, MCPError> + Send  + Sync + 'static,
     {
         //check if the tool exists
-        if !self.config.tools.iter().any(|tool| tool.name == tool_name) {
+        let Some(tool) = self.config.tools.iter().find(|tool| tool.name == tool_name) else {
             return Err(MCPError::Transport(format!(
                 "Tool {} not found in server config",
                 tool_name
             )));
-        }
+        };
+        self.insert_tool_handler(tool, handler)
+    }
+
+    fn insert_tool_handler<F>(&self, tool: &Tool, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(Value,Sender<TaskEvent<(LoadType,String),i32>>,Receiver<String>) -> Result<Value, MCPError> + Send  + Sync + 'static,
+    {
+        let validator = SchemaValidator::new(mcp_to_value(&tool.input_schema)?);
 
         //register the tool handler
         let mut handlers = match self.tool_handlers.try_lock() {
@@ -231,8 +449,112 @@ impl Server {
 
         let handler: Arc<Box<dyn Fn(Value, Sender<TaskEvent<(LoadType,String), i32>>,Receiver<String>) -> Result<Value, MCPError> + Send + Sync>> =
             Arc::new(Box::new(handler));
-        handlers.insert(tool_name, handler);
+        handlers.insert(tool.name.clone(), handler);
+        self.tool_validators.lock().unwrap().insert(tool.name.clone(), Arc::new(validator));
+
+        Ok(())
+    }
+
+    /// Serve `resources/read` for a resource listed in the server config.
+    pub fn register_resource_handler<F>(&self, uri: String, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(String) -> Result<Vec<ResourceContents>, MCPError> + Send + Sync + 'static,
+    {
+        if !self.config.resources.iter().any(|resource| resource.uri == uri) {
+            return Err(MCPError::Transport(format!(
+                "Resource {} not found in server config",
+                uri
+            )));
+        }
+
+        let handler: ResourceHandler = Arc::new(Box::new(move |uri, _variables| handler(uri)));
+        self.resource_handlers.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock resource handlers".to_string()))?
+            .insert(uri, handler);
+        Ok(())
+    }
+
+    /// Serve `resources/read` for every URI matching a template listed in the
+    /// server config. Templates are tried in registration order, after the
+    /// static resources.
+    pub fn register_resource_template_handler<F>(&self, uri_template: String, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(String, HashMap<String, String>) -> Result<Vec<ResourceContents>, MCPError> + Send + Sync + 'static,
+    {
+        if !self.config.resource_templates.iter().any(|template| template.uri_template == uri_template) {
+            return Err(MCPError::Transport(format!(
+                "Resource template {} not found in server config",
+                uri_template
+            )));
+        }
+
+        let template = UriTemplate::parse(&uri_template)?;
+        let handler: ResourceHandler = Arc::new(Box::new(handler));
+        self.template_handlers.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock resource handlers".to_string()))?
+            .push((template, handler));
+        Ok(())
+    }
+
+    /// Render prompts/get for a prompt listed in the server config. Required
+    /// arguments are checked before the handler runs.
+    pub fn register_prompt_handler<F>(&self, prompt_name: String, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(HashMap<String, String>) -> Result<GetPromptResult, MCPError> + Send + Sync + 'static,
+    {
+        if !self.config.prompts.iter().any(|prompt| prompt.name == prompt_name) {
+            return Err(MCPError::Transport(format!(
+                "Prompt {} not found in server config",
+                prompt_name
+            )));
+        }
 
+        self.prompt_handlers.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock prompt handlers".to_string()))?
+            .insert(prompt_name, Arc::new(Box::new(handler)));
+        Ok(())
+    }
+
+    /// Suggest values for an argument of a prompt listed in the server config.
+    pub fn register_prompt_completion<F>(&self, prompt_name: String, argument: String, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(String) -> Result<Vec<String>, MCPError> + Send + Sync + 'static,
+    {
+        let prompt = self.config.prompts.iter().find(|prompt| prompt.name == prompt_name)
+            .ok_or_else(|| MCPError::Transport(format!("Prompt {} not found in server config", prompt_name)))?;
+        if !prompt.arguments.iter().flatten().any(|arg| arg.name == argument) {
+            return Err(MCPError::Transport(format!(
+                "Prompt {} has no argument {}",
+                prompt_name, argument
+            )));
+        }
+        self.add_completion("ref/prompt", prompt_name, argument, Arc::new(Box::new(handler)))
+    }
+
+    /// Suggest values for a variable of a resource template listed in the server config.
+    pub fn register_resource_completion<F>(&self, uri_template: String, variable: String, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(String) -> Result<Vec<String>, MCPError> + Send + Sync + 'static,
+    {
+        if !self.config.resource_templates.iter().any(|template| template.uri_template == uri_template) {
+            return Err(MCPError::Transport(format!(
+                "Resource template {} not found in server config",
+                uri_template
+            )));
+        }
+        if !UriTemplate::parse(&uri_template)?.variables().any(|name| name == variable) {
+            return Err(MCPError::Transport(format!(
+                "Resource template {} has no variable {}",
+                uri_template, variable
+            )));
+        }
+        self.add_completion("ref/resource", uri_template, variable, Arc::new(Box::new(handler)))
+    }
+
+    fn add_completion(&self, kind: &str, target: String, argument: String, handler: CompletionHandler) -> Result<(), MCPError> {
+        self.completion_handlers.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock completion handlers".to_string()))?
+            .insert((kind.to_string(), target, argument), handler);
         Ok(())
     }
 
@@ -296,99 +618,12 @@ impl Server {
                 let jobs = job_manager.lock().unwrap().borrow_mut().polling();
 
                 match jobs {
-                    Ok(gn) => {
-                        for payload in gn {
-                            match &payload.1 {
-                               LoadType::Text => {
-                                    let tool_result = CallToolResult {
-                                        is_error: Some(false),
-                                        content: vec![ToolResultContent::Text(TextContent {
-                                            r#type: "text".to_string(),
-                                            text: payload.2.data.unwrap(),
-                                            annotations: None,
-                                        })],
-                                    };
-                                    let response = JSONRPCResponse::new(
-                                        payload.0,
-                                        serde_json::to_value(tool_result).map_err(MCPError::Serialization).unwrap(),
-                                    );
-                                    let response = serde_json::to_string(&response).map_err(MCPError::Serialization).unwrap();
-
-                                    server.handle_outbound(Some(PayLoad {
-                                        data: Some(response),
-                                        ctx: payload.2.ctx,
-                                    })).expect("panic");
-                                }
-                               LoadType::Audio => {
-                                   let tool_result = CallToolResult {
-                                       is_error: Some(false),
-                                       content: vec![ToolResultContent::Audio(AudioContent {
-                                           r#type: "audio".to_string(),
-                                           data: payload.2.data.unwrap(),
-                                           annotations: None,
-                                           mime_type: "audio/mpeg".to_string(),
-                                       })],
-                                   };
-                                   let response = JSONRPCResponse::new(
-                                       payload.0,
-                                       serde_json::to_value(tool_result).map_err(MCPError::Serialization).unwrap(),
-                                   );
-                                   let response = serde_json::to_string(&response).map_err(MCPError::Serialization).unwrap();
-
-                                   server.handle_outbound(Some(PayLoad {
-                                       data: Some(response),
-                                       ctx: payload.2.ctx,
-                                   })).expect("panic");
-                               }
-                               LoadType::Image => {
-                                   let tool_result = CallToolResult {
-                                       is_error: Some(false),
-                                       content: vec![ToolResultContent::Image(ImageContent {
-                                           r#type: "image".to_string(),
-                                           data: payload.2.data.unwrap(),
-                                           annotations: None,
-                                           mime_type: "image/png".to_string(),
-                                       })],
-                                   };
-                                   let response = JSONRPCResponse::new(
-                                       payload.0,
-                                       serde_json::to_value(tool_result).map_err(MCPError::Serialization).unwrap(),
-                                   );
-                                   let response = serde_json::to_string(&response).map_err(MCPError::Serialization).unwrap();
-
-                                   server.handle_outbound(Some(PayLoad {
-                                       data: Some(response),
-                                       ctx: payload.2.ctx,
-                                   })).expect("panic");
-                               }
-                               LoadType::Embedded => {
-                                   let content = payload.2.data.unwrap();
-                                   let result  =  serde_json::from_str::<ResourceContents>(&content);
-                                   if  let Err(err) = result {
-                                       return Err(err);
-                                   }
-
-                                   let resource = result.unwrap();
-                                   let tool_result = CallToolResult {
-                                       is_error: Some(false),
-                                       content: vec![ToolResultContent::Resource(EmbeddedResource {
-                                           r#type: "resource".to_string(),
-                                           annotations: None,
-                                           resource,
-                                       })],
-                                   };
-
-                                   let response = JSONRPCResponse::new(
-                                       payload.0,
-                                       serde_json::to_value(tool_result).map_err(MCPError::Serialization).unwrap(),
-                                   );
-                                   let response = serde_json::to_string(&response).map_err(MCPError::Serialization).unwrap();
-
-                                   server.handle_outbound(Some(PayLoad {
-                                       data: Some(response),
-                                       ctx: payload.2.ctx,
-                                   })).expect("panic");
-                               }
+                    //nothing to forward, don't spin the cpu away from the handlers
+                    Ok(finished) if finished.is_empty() => std::thread::sleep(Duration::from_millis(1)),
+                    Ok(finished) => {
+                        for job in finished {
+                            if let Err(e) = server.send_tool_result(job) {
+                                log::error!("Failed to send tool result: {}", e);
                             }
                         }
                     },
@@ -401,7 +636,7 @@ impl Server {
                     Ok(reader) => {
                         let event  = reader.try_recv();
                         if let Ok(_) = event {
-                            break Ok(());
+                            break;
                         }
                     }
                     Err(_) => {}
@@ -495,6 +730,15 @@ impl Server {
     }
 
     fn handle_outbound(&self, message: Option<rioc::PayLoad>) -> Result<(), String> {
+        //without explicit context, reply on the session currently being served
+        let message = message.map(|mut payload| {
+            if payload.ctx.is_none() {
+                let mut ctx = ChainContext { data: HashMap::new() };
+                ctx.data.insert(SESSION_ID_KEY.to_string(), get_current_session());
+                payload.ctx = Some(ctx);
+            }
+            payload
+        });
         self.chain.with_read(|layer| {
             let _ = layer.handle_outbound(message);
         });
@@ -502,11 +746,9 @@ impl Server {
     }
 
     fn handle_inbound(&self) -> Result<(), String> {
-        // let mut result: Result<LayerResult,String> = Err("".to_string());
         self.chain.with_read(|layer| {
-            let _ = layer.handle_inbound(None);
-        });
-        Ok(())
+            layer.handle_inbound(None).map(|_| ())
+        })
     }
 
     fn handle_message(&mut self, ctx: Option<ChainContext> ,message: JSONRPCMessage) -> Result<(), MCPError> {
@@ -530,6 +772,9 @@ impl Server {
                 match method.as_str() {
                     "initialize" => {
                         info!("Received initialize request");
+                        let capabilities = params.as_ref()
+                            .and_then(|params| params.get("capabilities"))
+                            .map(|capabilities| capabilities.to_string());
                         if let Err(e) = self.handle_initialize(id.clone(), params) {
                             log::error!("Failed to handle initialize request: {}", e);
                             self.response_with_error(id,error_codes::INVALID_REQUEST, "Failed to handle initialize request".to_string(),None);
@@ -544,6 +789,10 @@ impl Server {
                         }else {
                             SESSION_STORE.create_session(session_id, 60*30);
                         } 
+                        if let Some(capabilities) = capabilities {
+                            SESSION_STORE.set_session_value(&get_current_session(), CLIENT_CAPABILITIES_KEY.to_string(), capabilities);
+                        }
+                        self.sessions.entry(get_current_session()).or_default();
                     }
                     "ping" => {
                         info!("Received ping request");
@@ -579,6 +828,72 @@ impl Server {
                             self.response_with_error(id,error_codes::INVALID_REQUEST, "Failed to call tool".to_string(),None);
                         }
                     }
+                    "resources/list" | "resources/templates/list" | "resources/read" => {
+                        info!("Received {} request", method);
+                        if let Err(e) = self.check_state(id.clone()) {
+                            log::error!("Failed to check state: {}", e);
+                            self.response_with_error(id,error_codes::INVALID_REQUEST, "Cannot access resources at current state.  Please initialize the session first".to_string(),None);
+                            return Err(e);
+                        }
+
+                        let result = match method.as_str() {
+                            "resources/list" => self.handle_list_resources(id.clone(), params),
+                            "resources/templates/list" => self.handle_list_resource_templates(id.clone(), params),
+                            _ => self.handle_read_resource(id.clone(), params),
+                        };
+                        if let Err(e) = result {
+                            log::error!("Failed to handle {} request: {}", method, e);
+                            self.response_with_error(id,error_codes::INVALID_PARAMS, e.to_string(),None);
+                        }
+                    }
+                    "prompts/list" | "prompts/get" => {
+                        info!("Received {} request", method);
+                        if let Err(e) = self.check_state(id.clone()) {
+                            log::error!("Failed to check state: {}", e);
+                            self.response_with_error(id,error_codes::INVALID_REQUEST, "Cannot access prompts at current state.  Please initialize the session first".to_string(),None);
+                            return Err(e);
+                        }
+
+                        let result = if method == "prompts/list" {
+                            self.handle_list_prompts(id.clone(), params)
+                        } else {
+                            self.handle_get_prompt(id.clone(), params)
+                        };
+                        if let Err(e) = result {
+                            log::error!("Failed to handle {} request: {}", method, e);
+                            self.response_with_error(id,error_codes::INVALID_PARAMS, e.to_string(),None);
+                        }
+                    }
+                    "completion/complete" => {
+                        info!("Received completion/complete request");
+                        if let Err(e) = self.check_state(id.clone()) {
+                            log::error!("Failed to check state: {}", e);
+                            self.response_with_error(id,error_codes::INVALID_REQUEST, "Cannot complete arguments at current state.  Please initialize the session first".to_string(),None);
+                            return Err(e);
+                        }
+
+                        if let Err(e) = self.handle_complete(id.clone(), params) {
+                            log::error!("Failed to handle completion/complete request: {}", e);
+                            self.response_with_error(id,error_codes::INVALID_PARAMS, e.to_string(),None);
+                        }
+                    }
+                    "resources/subscribe" | "resources/unsubscribe" => {
+                        info!("Received {} request", method);
+                        if let Err(e) = self.check_state(id.clone()) {
+                            log::error!("Failed to check state: {}", e);
+                            self.response_with_error(id,error_codes::INVALID_REQUEST, "Cannot access resources at current state.  Please initialize the session first".to_string(),None);
+                            return Err(e);
+                        }
+                        if !self.config.resource_subscriptions {
+                            self.response_with_error(id,error_codes::METHOD_NOT_FOUND, "Resource subscriptions are not enabled".to_string(),None);
+                            return Ok(());
+                        }
+
+                        if let Err(e) = self.handle_subscription(id.clone(), method == "resources/subscribe", params) {
+                            log::error!("Failed to handle {} request: {}", method, e);
+                            self.response_with_error(id,error_codes::INVALID_PARAMS, e.to_string(),None);
+                        }
+                    }
                     "shutdown" => {
                         info!("Received shutdown request");
                         if let Err(e) = self.check_state(id.clone()) {
@@ -589,6 +904,8 @@ impl Server {
                         if let Err(e) = self.handle_shutdown(id, params) {
                             log::error!("Failed to handle shutdown request: {}", e);
                         }
+                        self.sessions.remove(&get_current_session());
+                        self.roots.remove(&get_current_session());
                         let tx = self.notify.clone_tx();
 
                         if let Ok(mut tx) = tx {
@@ -628,7 +945,7 @@ impl Server {
                     }
                     "notifications/roots/list_changed" => {
                         info!("Received notifications/roots/list_changed request");
-                        if let Err(e) = self.handle_roots_list_changed(params) {
+                        if let Err(e) = self.handle_roots_list_changed(ctx.as_ref(), params) {
                             log::error!(
                                 "Failed to handle notifications/roots/list_changed request: {}",
                                 e
@@ -655,11 +972,17 @@ impl Server {
                     }
                 }
             }
-            JSONRPCMessage::Error(_) => {
-                self.cache_response(message);
+            JSONRPCMessage::Error(ref error) => {
+                let id = error.id.clone();
+                if let Some(message) = self.resolve_pending(ctx.as_ref(), &id, message) {
+                    self.cache_response(message);
+                }
             }
-            JSONRPCMessage::Response(_) => {
-                self.cache_response(message);
+            JSONRPCMessage::Response(ref response) => {
+                let id = response.id.clone();
+                if let Some(message) = self.resolve_pending(ctx.as_ref(), &id, message) {
+                    self.cache_response(message);
+                }
             }
         }
 
@@ -706,8 +1029,21 @@ impl Server {
         let capabilities = ServerCapabilities {
             experimental: None,
             logging: Some(Value::Bool(false)),
-            prompts: None,
-            resources: None,
+            prompts: if !self.config.prompts.is_empty() {
+                Some(PromptsCapability {
+                    list_changed: Some(false),
+                })
+            } else {
+                None
+            },
+            resources: if !self.config.resources.is_empty() || !self.config.resource_templates.is_empty() {
+                Some(ResourcesCapability {
+                    subscribe: Some(self.config.resource_subscriptions),
+                    list_changed: Some(self.config.resource_subscriptions),
+                })
+            } else {
+                None
+            },
             tools: if !self.config.tools.is_empty() {
                 Some(ToolsCapability {
                     list_changed: Some(false),
@@ -715,6 +1051,11 @@ impl Server {
             } else {
                 None
             },
+            completions: if !self.completion_handlers.lock().unwrap().is_empty() {
+                Some(json!({}))
+            } else {
+                None
+            },
         };
 
         let server_info = Implementation {
@@ -767,16 +1108,26 @@ impl Server {
     }
 
     fn handle_tool_call(&mut self, ctx: Option<ChainContext>, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
-        let params = params.ok_or_else(|| {
-            MCPError::Transport("Missing parameters in tools/call request".to_string())
-        })?;
-
-        //parse the parameters as CallToolParams
-        let call_params: CallToolParams = serde_json::from_value(params.clone())
-            .map_err(|e| MCPError::Transport(format!("Invalid tools/call parameters: {}", e)))?;
+        //malformed calls and unknown tools are protocol errors, failures of
+        //the tool itself are reported in the result
+        let call_params = match params.map(serde_json::from_value::<CallToolParams>) {
+            Some(Ok(call_params)) => call_params,
+            Some(Err(e)) => {
+                self.response_with_error(id, error_codes::INVALID_PARAMS, format!("Invalid tools/call parameters: {}", e), None);
+                return Ok(());
+            }
+            None => {
+                self.response_with_error(id, error_codes::INVALID_PARAMS, "Missing parameters in tools/call request".to_string(), None);
+                return Ok(());
+            }
+        };
 
         //get the tool by name
         let tool_name = call_params.name.clone();
+        if !self.config.tools.iter().any(|tool| tool.name == tool_name) {
+            self.response_with_error(id, error_codes::INVALID_PARAMS, format!("Unknown tool: {}", tool_name), None);
+            return Ok(());
+        }
 
         //convert arguments to JSON value if exists,otherwise use null
         let tool_params = match call_params.arguments {
@@ -784,29 +1135,333 @@ impl Server {
             None => Value::Null,
         };
 
-        let result = self.execute_tool(tool_name, tool_params);
+        //check the arguments before any handler sees them, a call without
+        //arguments is checked as an empty object
+        let arguments = if tool_params.is_null() { json!({}) } else { tool_params.clone() };
+        let validator = self.tool_validators.lock().unwrap().get(&tool_name).cloned();
+        let violations = validator.map(|validator| validator.validate(&arguments)).unwrap_or_default();
+        if !violations.is_empty() {
+            let details = violations.iter()
+                .map(|violation| format!("{}: {}", if violation.path.is_empty() { "/" } else { &violation.path }, violation.message))
+                .collect::<Vec<_>>();
+            self.response_with_error(
+                id,
+                error_codes::INVALID_PARAMS,
+                format!("Invalid arguments for tool {}: {}", tool_name, details.join("; ")),
+                Some(json!({"violations": violations})),
+            );
+            return Ok(());
+        }
+
+        let progress_token = call_params._meta.and_then(|meta| meta.progress_token);
+        let result = self.execute_tool(tool_name, tool_params, progress_token);
         match result {
             Ok(job) => {
                 self.job_manager.lock().unwrap().borrow_mut().add_job(id,(ctx,job))
             }
             Err(e) => {
-                let error = JSONRPCMessage::Error(JSONRPCError::new_with_details(
-                    id,
-                    -32000,
-                    format!("Tool execution failed: {}", e),
-                    None,
-                ));
-
-                let error = serde_json::to_string(&error).map_err(MCPError::Serialization)?;
-                if let Err(e) = self.handle_outbound(Some(rioc::PayLoad {
-                    data: Some(error),
-                    ctx: None,
-                })) {
-                    log::error!("Failed to send tool call error response: {}", e);
+                self.response_with_error(id, error_codes::INTERNAL_ERROR, format!("Tool execution failed: {}", e), None);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_list_resources(&self, id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+        let resources_list = ListResourcesResult {
+            next_cursor: None,
+            resources: self.config.resources.clone(),
+        };
+        self.send_result(id, mcp_to_value(resources_list)?)
+    }
+
+    fn handle_list_resource_templates(&self, id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+        let templates_list = ListResourceTemplatesResult {
+            next_cursor: None,
+            resource_templates: self.config.resource_templates.clone(),
+        };
+        self.send_result(id, mcp_to_value(templates_list)?)
+    }
+
+    fn handle_read_resource(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let params = params.ok_or_else(|| {
+            MCPError::Transport("Missing parameters in resources/read request".to_string())
+        })?;
+        let read_params: ReadResourceParams = serde_json::from_value(params)
+            .map_err(|e| MCPError::Transport(format!("Invalid resources/read parameters: {}", e)))?;
+        let uri = read_params.uri;
+
+        //exact resources win over templates that happen to match
+        let static_handler = self.resource_handlers.lock().unwrap().get(&uri).cloned();
+        let handler = static_handler.map(|handler| (handler, HashMap::new())).or_else(|| {
+            self.template_handlers.lock().unwrap().iter()
+                .find_map(|(template, handler)| template.matches(&uri).map(|variables| (handler.clone(), variables)))
+        });
+
+        let Some((handler, variables)) = handler else {
+            self.response_with_error(id, error_codes::RESOURCE_NOT_FOUND, "Resource not found".to_string(), Some(json!({"uri": uri})));
+            return Ok(());
+        };
+
+        match handler(uri, variables) {
+            Ok(contents) => self.send_result(id, mcp_to_value(ReadResourceResult { contents })?),
+            Err(e) => {
+                self.response_with_error(id, error_codes::INTERNAL_ERROR, format!("Failed to read resource: {}", e), None);
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_list_prompts(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let cursor = params
+            .and_then(|params| params.get("cursor").cloned())
+            .and_then(|cursor| cursor.as_str().map(str::to_string));
+
+        //cursors are the offset of the first prompt on the page
+        let start = match cursor {
+            Some(cursor) => cursor.parse::<usize>().ok()
+                .filter(|start| *start <= self.config.prompts.len())
+                .ok_or_else(|| MCPError::Transport(format!("Invalid cursor: {}", cursor)))?,
+            None => 0,
+        };
+        let end = match self.config.prompts_page_size {
+            Some(page_size) => (start + page_size).min(self.config.prompts.len()),
+            None => self.config.prompts.len(),
+        };
+
+        let prompts_list = ListPromptsResult {
+            next_cursor: (end < self.config.prompts.len()).then(|| end.to_string()),
+            prompts: self.config.prompts[start..end].to_vec(),
+        };
+        self.send_result(id, mcp_to_value(prompts_list)?)
+    }
+
+    fn handle_get_prompt(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let params = params.ok_or_else(|| {
+            MCPError::Transport("Missing parameters in prompts/get request".to_string())
+        })?;
+        let params: GetPromptParams = serde_json::from_value(params)
+            .map_err(|e| MCPError::Transport(format!("Invalid prompts/get parameters: {}", e)))?;
+
+        let prompt = self.config.prompts.iter().find(|prompt| prompt.name == params.name)
+            .ok_or_else(|| MCPError::Transport(format!("Unknown prompt: {}", params.name)))?;
+        let arguments: HashMap<String, String> = params.arguments
+            .map(|arguments| arguments.into_iter().collect())
+            .unwrap_or_default();
+
+        let missing: Vec<&str> = prompt.arguments.iter().flatten()
+            .filter(|argument| argument.required.unwrap_or(false) && !arguments.contains_key(&argument.name))
+            .map(|argument| argument.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.response_with_error(id, error_codes::INVALID_PARAMS, format!("Missing required arguments: {}", missing.join(", ")), Some(json!({"missing": missing})));
+            return Ok(());
+        }
+
+        let handler = self.prompt_handlers.lock().unwrap().get(&prompt.name).cloned();
+        let Some(handler) = handler else {
+            self.response_with_error(id, error_codes::INTERNAL_ERROR, format!("No handler registered for prompt {}", prompt.name), None);
+            return Ok(());
+        };
+
+        match handler(arguments) {
+            Ok(result) => self.send_result(id, mcp_to_value(result)?),
+            Err(e) => {
+                self.response_with_error(id, error_codes::INTERNAL_ERROR, format!("Failed to render prompt: {}", e), None);
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_complete(&self, id: RequestId, params: Option<Value>) -> Result<(), MCPError> {
+        let params = params.ok_or_else(|| {
+            MCPError::Transport("Missing parameters in completion/complete request".to_string())
+        })?;
+        let params: CompleteParams = serde_json::from_value(params)
+            .map_err(|e| MCPError::Transport(format!("Invalid completion/complete parameters: {}", e)))?;
+
+        let (kind, target) = match params.ref_ {
+            Reference::Prompt(prompt) => {
+                if !self.config.prompts.iter().any(|p| p.name == prompt.name) {
+                    return Err(MCPError::Transport(format!("Unknown prompt: {}", prompt.name)));
                 }
+                ("ref/prompt", prompt.name)
+            }
+            Reference::Resource(resource) => {
+                if !self.config.resource_templates.iter().any(|t| t.uri_template == resource.uri) {
+                    return Err(MCPError::Transport(format!("Unknown resource template: {}", resource.uri)));
+                }
+                ("ref/resource", resource.uri)
+            }
+        };
+
+        //arguments without a provider simply have no suggestions
+        let handler = self.completion_handlers.lock().unwrap()
+            .get(&(kind.to_string(), target, params.argument.name))
+            .cloned();
+        let mut values = match handler {
+            Some(handler) => match handler(params.argument.value) {
+                Ok(values) => values,
+                Err(e) => {
+                    self.response_with_error(id, error_codes::INTERNAL_ERROR, format!("Failed to complete argument: {}", e), None);
+                    return Ok(());
+                }
+            },
+            None => Vec::new(),
+        };
+
+        let total = values.len();
+        values.truncate(MAX_COMPLETION_VALUES);
+        let result = CompleteResult {
+            _meta: None,
+            completion: CompletionInfo {
+                values,
+                total: Some(total as u32),
+                has_more: Some(total > MAX_COMPLETION_VALUES),
+            },
+        };
+        self.send_result(id, mcp_to_value(result)?)
+    }
+
+    fn handle_subscription(&self, id: RequestId, subscribe: bool, params: Option<Value>) -> Result<(), MCPError> {
+        let params = params.ok_or_else(|| {
+            MCPError::Transport("Missing parameters in resources subscription request".to_string())
+        })?;
+        //subscribe and unsubscribe carry the same params
+        let params: SubscribeParams = serde_json::from_value(params)
+            .map_err(|e| MCPError::Transport(format!("Invalid subscription parameters: {}", e)))?;
+
+        let known = self.config.resources.iter().any(|resource| resource.uri == params.uri)
+            || self.template_handlers.lock().unwrap().iter().any(|(template, _)| template.matches(&params.uri).is_some());
+        if subscribe && !known {
+            self.response_with_error(id, error_codes::RESOURCE_NOT_FOUND, "Resource not found".to_string(), Some(json!({"uri": params.uri})));
+            return Ok(());
+        }
+
+        let mut uris = self.sessions.entry(get_current_session()).or_default();
+        if subscribe {
+            uris.insert(params.uri);
+        } else {
+            uris.remove(&params.uri);
+        }
+        drop(uris);
+
+        self.send_result(id, json!(EmptyResult::new()))
+    }
+
+    /// Send notifications/resources/updated to every session subscribed to `uri`.
+    pub fn notify_resource_updated(&self, uri: &str) -> Result<(), MCPError> {
+        let notification = ServerNotification::ResourceUpdatedNotification(ResourceUpdatedNotification::new(
+            ResourceUpdatedParams { uri: uri.to_string() },
+        ));
+        self.notify_sessions(notification, |uris| uris.contains(uri))
+    }
+
+    /// Send notifications/resources/list_changed to every initialized session.
+    pub fn notify_resource_list_changed(&self) -> Result<(), MCPError> {
+        let notification = ServerNotification::ResourceListChangedNotification(ResourceListChangedNotification::new());
+        self.notify_sessions(notification, |_| true)
+    }
+
+    fn notify_sessions<F>(&self, notification: ServerNotification, filter: F) -> Result<(), MCPError>
+    where
+        F: Fn(&HashSet<String>) -> bool,
+    {
+        if !self.config.resource_subscriptions {
+            return Err(MCPError::UnsupportedFeature("Resource subscriptions are not enabled".to_string()));
+        }
+
+        //forget sessions that expired or were closed by their transport
+        self.sessions.retain(|session_id, _| SESSION_STORE.get_session(session_id).is_some());
+
+        let targets: Vec<String> = self.sessions.iter()
+            .filter(|entry| filter(entry.value()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let notification = serde_json::to_string(&build_server_notification(notification))
+            .map_err(MCPError::Serialization)?;
+        for session_id in targets {
+            let mut ctx = ChainContext { data: HashMap::new() };
+            ctx.data.insert(SESSION_ID_KEY.to_string(), session_id);
+            if let Err(e) = self.handle_outbound(Some(rioc::PayLoad {
+                data: Some(notification.clone()),
+                ctx: Some(ctx),
+            })) {
+                log::error!("Failed to send resource notification: {}", e);
             }
         }
+        Ok(())
+    }
+
+    /// Answer a finished tool call with everything its handler produced.
+    fn send_tool_result(&self, job: FinishedJob) -> Result<(), MCPError> {
+        let content = job.parts.into_iter()
+            .map(|(load_type, data)| Self::tool_content(load_type, data))
+            .collect::<Result<Vec<_>, _>>();
+        let response = match content {
+            Ok(content) => {
+                let tool_result = CallToolResult {
+                    is_error: Some(job.failed),
+                    content,
+                };
+                JSONRPCMessage::Response(JSONRPCResponse::new(job.id, mcp_to_value(tool_result)?))
+            }
+            Err(e) => JSONRPCMessage::Error(JSONRPCError::new_with_details(
+                job.id,
+                error_codes::INTERNAL_ERROR,
+                format!("Invalid tool output: {}", e),
+                None,
+            )),
+        };
+
+        let response = serde_json::to_string(&response).map_err(MCPError::Serialization)?;
+        self.handle_outbound(Some(PayLoad {
+            data: Some(response),
+            ctx: job.ctx,
+        })).map_err(MCPError::Transport)
+    }
+
+    fn tool_content(load_type: LoadType, data: String) -> Result<ToolResultContent, MCPError> {
+        let content = match load_type {
+            LoadType::Text => ToolResultContent::Text(TextContent {
+                r#type: "text".to_string(),
+                text: data,
+                annotations: None,
+            }),
+            LoadType::Audio => ToolResultContent::Audio(AudioContent {
+                r#type: "audio".to_string(),
+                data,
+                annotations: None,
+                mime_type: "audio/mpeg".to_string(),
+            }),
+            LoadType::Image => ToolResultContent::Image(ImageContent {
+                r#type: "image".to_string(),
+                data,
+                annotations: None,
+                mime_type: "image/png".to_string(),
+            }),
+            LoadType::Embedded => ToolResultContent::Resource(EmbeddedResource {
+                r#type: "resource".to_string(),
+                annotations: None,
+                resource: serde_json::from_str::<ResourceContents>(&data).map_err(MCPError::Serialization)?,
+            }),
+        };
+        Ok(content)
+    }
 
+    fn send_result(&self, id: RequestId, result: Value) -> Result<(), MCPError> {
+        let response = JSONRPCResponse::new(id, result);
+        let response = serde_json::to_string(&response).map_err(MCPError::Serialization)?;
+        if let Err(e) = self.handle_outbound(Some(rioc::PayLoad {
+            data: Some(response),
+            ctx: None,
+        })) {
+            log::error!("Failed to send response: {}", e);
+        }
         Ok(())
     }
 
@@ -858,11 +1513,32 @@ impl Server {
         Ok(())
     }
 
-    fn execute_tool(&self, tool: String, params: Value) -> Result<JobTask<(LoadType,String),i32,String>, MCPError> {
+    fn execute_tool(&self, tool: String, params: Value, progress_token: Option<ProgressToken>) -> Result<JobTask<(LoadType,String),i32,String>, MCPError> {
         let handlers = self.tool_handlers.lock().unwrap();
         if let Some(handler) = handlers.get(&tool).cloned() {
+            let tool_name = tool.clone();
+            //handlers run off the message thread, carry the session over so
+            //calls like create_message reach the right client
+            let session_id = get_current_session();
             let job: JobTask<(LoadType,String),i32,String> = JobTask::new(params,move |params,sender,receiver| {
-                let _result = handler(params,sender,receiver);
+                set_session_id(session_id);
+                PROGRESS_TOKEN.with(|token| *token.borrow_mut() = progress_token);
+                //a returned value is the last content part, after whatever was sent,
+                //an error becomes that part and flags the result
+                match handler(params,sender.clone(),receiver) {
+                    Ok(Value::Null) => {}
+                    Ok(Value::String(text)) => {
+                        let _ = sender.send(TaskEvent::Data((LoadType::Text, text)));
+                    }
+                    Ok(value) => {
+                        let _ = sender.send(TaskEvent::Data((LoadType::Text, value.to_string())));
+                    }
+                    Err(e) => {
+                        log::error!("Tool {} failed: {}", tool_name, e);
+                        let _ = sender.send(TaskEvent::Data((LoadType::Text, e.to_string())));
+                        let _ = sender.send(TaskEvent::Error(error_codes::INTERNAL_ERROR));
+                    }
+                }
             });
 
             return Ok(job);
@@ -931,7 +1607,21 @@ impl Server {
         Ok(Value::Null)
     }
 
-    fn handle_roots_list_changed(&self, _params: Option<Value>) -> Result<Value, MCPError> {
+    fn handle_roots_list_changed(&self, ctx: Option<&ChainContext>, _params: Option<Value>) -> Result<Value, MCPError> {
+        let session_id = Self::session_of(ctx);
+        {
+            let mut entry = self.roots.entry(session_id.clone()).or_insert((0, None));
+            entry.0 += 1;
+            entry.1 = None;
+        }
+
+        //the roots/list response is delivered on this thread, refresh from another
+        let server = self.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = server.fetch_roots(&session_id) {
+                log::warn!("Failed to refresh roots of session {}: {}", session_id, e);
+            }
+        });
         Ok(Value::Null)
     }
 
@@ -1004,3 +1694,84 @@ impl Server {
         return s;
     }
 }
+
+/// Sends notifications/progress for a tool call to the session that made it.
+/// Updates closer together than MIN_PROGRESS_INTERVAL are dropped, except the
+/// one reaching the total and the last one, which is held back until the
+/// reporter is flushed or dropped.
+pub struct ProgressReporter {
+    server: Server,
+    session_id: String,
+    token: Option<ProgressToken>,
+    last_progress: Option<f64>,
+    last_sent: Option<Instant>,
+    /// Latest update held back by the throttle.
+    pending: Option<(f64, Option<f64>, Option<String>)>,
+}
+
+impl ProgressReporter {
+    /// Whether the caller supplied a progress token.
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    pub fn report(&mut self, progress: f64, total: Option<f64>, message: Option<&str>) -> Result<(), MCPError> {
+        if self.token.is_none() {
+            return Ok(());
+        }
+
+        //progress has to increase with every notification
+        if self.last_progress.is_some_and(|last| progress <= last) {
+            return Ok(());
+        }
+        self.last_progress = Some(progress);
+        let message = message.map(|message| message.to_string());
+        let finished = total.is_some_and(|total| progress >= total);
+        if !finished && self.last_sent.is_some_and(|sent| sent.elapsed() < MIN_PROGRESS_INTERVAL) {
+            self.pending = Some((progress, total, message));
+            return Ok(());
+        }
+        self.pending = None;
+        self.send(progress, total, message)
+    }
+
+    /// Send the update held back by the throttle, if any. Called on drop, so
+    /// the last update of a handler is never lost.
+    pub fn flush(&mut self) -> Result<(), MCPError> {
+        match self.pending.take() {
+            Some((progress, total, message)) => self.send(progress, total, message),
+            None => Ok(()),
+        }
+    }
+
+    fn send(&mut self, progress: f64, total: Option<f64>, message: Option<String>) -> Result<(), MCPError> {
+        let Some(token) = self.token.clone() else {
+            return Ok(());
+        };
+        let notification = ServerNotification::ProgressNotification(ProgressNotification::new(ProgressParams {
+            progress_token: token,
+            progress,
+            total,
+            message,
+        }));
+        let notification = serde_json::to_string(&build_server_notification(notification))
+            .map_err(MCPError::Serialization)?;
+        let mut ctx = ChainContext { data: HashMap::new() };
+        ctx.data.insert(SESSION_ID_KEY.to_string(), self.session_id.clone());
+        self.server.handle_outbound(Some(rioc::PayLoad {
+            data: Some(notification),
+            ctx: Some(ctx),
+        })).map_err(MCPError::Transport)?;
+
+        self.last_sent = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::warn!("Failed to send the last progress update: {}", e);
+        }
+    }
+}
@@ -36,6 +36,10 @@ pub mod error {
 
         #[error("Timeout error: {0}")]
         Timeout(String),
+
+        /// The transport shut down or its peer went away, nothing more will arrive.
+        #[error("Transport closed: {0}")]
+        Closed(String),
     }
 }
 
@@ -1,12 +1,38 @@
 use std::{sync::{Arc}};
 use dashmap::DashMap;
 use log::warn;
-use rioc::{ChainContext, JobTask, PayLoad, TaskEvent};
+use rioc::{ChainContext, JobTask, TaskEvent};
 use crate::schema::schema::{LoadType, RequestId};
 
+/// Content parts a tool handler produced, in the order it sent them.
+pub type ToolOutput = Vec<(LoadType, String)>;
+
+/// A tool call whose handler is done, ready to be answered.
+pub struct FinishedJob {
+    pub id: RequestId,
+    pub ctx: Option<ChainContext>,
+    pub parts: ToolOutput,
+    /// The handler sent an error or panicked, answer with isError set.
+    pub failed: bool,
+}
+
+/// What a running handler produced so far.
+#[derive(Default)]
+struct JobOutput {
+    parts: ToolOutput,
+    failed: bool,
+}
+
+/// A running tool call and the context its answer goes out with.
+struct JobEntry {
+    ctx: Option<ChainContext>,
+    task: JobTask<(LoadType, String), i32, String>,
+    output: JobOutput,
+}
+
 #[derive(Clone)]
 pub struct JobManager {
-    jobs: Arc<DashMap<RequestId, (Option<ChainContext>, JobTask<(LoadType, String), i32, String>)>>,
+    jobs: Arc<DashMap<RequestId, JobEntry>>,
 }
 
 impl JobManager {
@@ -15,13 +41,17 @@ impl JobManager {
     }
 
     pub fn add_job(&mut self,req: RequestId, job: (Option<ChainContext>,JobTask<(LoadType,String),i32,String>)) {
-        self.jobs.insert(req, job);
+        self.jobs.insert(req, JobEntry {
+            ctx: job.0,
+            task: job.1,
+            output: JobOutput::default(),
+        });
     }
 
     pub fn cancel_job(&mut self, req: RequestId) {
         let job  = self.jobs.remove(&req);
         if let Some(mut job) = job {
-            job.1.1.cancel()
+            job.1.task.cancel()
         } else {
             warn!("No job found with request {:?}", req);
         }
@@ -29,40 +59,62 @@ impl JobManager {
 
     pub fn cancel_all_jobs(&mut self) {
         for mut job in self.jobs.iter_mut() {
-            job.value_mut().1.cancel();
+            job.value_mut().task.cancel();
         }
         self.jobs.clear();
     }
 
-    pub fn polling(&mut self) -> Result<Vec<(RequestId,LoadType,PayLoad)>, String> {
+    /// Collect what running handlers sent so far and hand back the jobs that
+    /// are done, so each tool call is answered once with all of its output.
+    pub fn polling(&mut self) -> Result<Vec<FinishedJob>, String> {
         let mut to_remove = Vec::new();
-        let mut payloads = vec![];
+        let mut cancelled = Vec::new();
 
         for mut entry in self.jobs.iter_mut() {
             let req = entry.key().clone();
-            let (ctx, job) = entry.value_mut();
-            if let Some(event) = job.try_recv() {
+            let JobEntry { task, output, .. } = entry.value_mut();
+            while let Some(event) = task.try_recv() {
                 match event {
                     TaskEvent::Data(data) => {
-                        let payload = PayLoad {
-                            data: Some(data.1),
-                            ctx: ctx.clone(),
-                        };
-                        payloads.push((req.clone(), data.0, payload));
+                        output.parts.push(data);
+                    },
+                    TaskEvent::Error(code) => {
+                        warn!("Job {:?} failed with code {}", req, code);
+                        output.failed = true;
+                    },
+                    TaskEvent::Panic(message) => {
+                        warn!("Job {:?} panicked: {}", req, message);
+                        output.parts.push((LoadType::Text, "Tool handler panicked".to_string()));
+                        output.failed = true;
+                        to_remove.push(req.clone());
+                        break;
                     },
                     TaskEvent::Done => {
                         to_remove.push(req.clone());
+                        break;
                     },
-                    _ => {}
+                    //a cancelled request gets no response
+                    TaskEvent::Cancelled => {
+                        cancelled.push(req.clone());
+                        break;
+                    },
+                    TaskEvent::Progress(_) => {}
                 }
             }
         }
 
-        for req in to_remove {
+        for req in cancelled {
             self.jobs.remove(&req);
         }
 
-        Ok(payloads)
+        let mut finished = vec![];
+        for req in to_remove {
+            if let Some((id, JobEntry { ctx, output, .. })) = self.jobs.remove(&req) {
+                finished.push(FinishedJob { id, ctx, parts: output.parts, failed: output.failed });
+            }
+        }
+
+        Ok(finished)
     }
 }
 
@@ -0,0 +1,318 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Checks tool arguments against a tool's input schema. Covers the part of
+//! JSON Schema draft 2020-12 tool schemas use: type, enum, const, required,
+//! properties, additionalProperties, items, string, number and array bounds,
+//! and pattern.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Longest string, in bytes, a `pattern` is matched against. Longer ones
+/// are rejected without running the regex.
+pub const MAX_PATTERN_INPUT: usize = 64 * 1024;
+
+/// A value that does not satisfy the schema, located by a JSON Pointer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+/// A schema with its patterns compiled, built once per tool and reused for
+/// every call.
+#[derive(Debug, Clone)]
+pub struct SchemaValidator {
+    schema: Value,
+    patterns: HashMap<String, Regex>,
+}
+
+impl SchemaValidator {
+    pub fn new(schema: Value) -> Self {
+        let mut patterns = HashMap::new();
+        collect_patterns(&schema, &mut patterns);
+        SchemaValidator { schema, patterns }
+    }
+
+    /// Every violation of the schema by `instance`, empty when it is valid.
+    pub fn validate(&self, instance: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        self.validate_at(&self.schema, instance, "", &mut violations);
+        violations
+    }
+
+    fn validate_at(&self, schema: &Value, instance: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+        let schema = match schema {
+            Value::Object(schema) => schema,
+            Value::Bool(false) => {
+                violations.push(violation(path, "no value is allowed here".to_string()));
+                return;
+            }
+            _ => return,
+        };
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|name| has_type(instance, name)) {
+                violations.push(violation(path, format!("expected {}, got {}", types.join(" or "), type_name(instance))));
+                //the remaining keywords would only repeat the mismatch
+                return;
+            }
+        }
+
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(instance) {
+                violations.push(violation(path, format!("must be one of {}", Value::Array(allowed.clone()))));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != instance {
+                violations.push(violation(path, format!("must be {}", expected)));
+            }
+        }
+
+        match instance {
+            Value::Object(object) => {
+                if let Some(Value::Array(required)) = schema.get("required") {
+                    for name in required.iter().filter_map(Value::as_str) {
+                        if !object.contains_key(name) {
+                            violations.push(violation(&child(path, name), "is required".to_string()));
+                        }
+                    }
+                }
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (name, value) in object {
+                    match properties.and_then(|properties| properties.get(name)) {
+                        Some(property) => self.validate_at(property, value, &child(path, name), violations),
+                        None => match schema.get("additionalProperties") {
+                            Some(Value::Bool(false)) => {
+                                violations.push(violation(&child(path, name), "is not an allowed property".to_string()));
+                            }
+                            Some(additional) => self.validate_at(additional, value, &child(path, name), violations),
+                            None => {}
+                        },
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                    if (items.len() as u64) < min {
+                        violations.push(violation(path, format!("must have at least {} items", min)));
+                    }
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    if items.len() as u64 > max {
+                        violations.push(violation(path, format!("must have at most {} items", max)));
+                    }
+                }
+                if schema.get("uniqueItems") == Some(&Value::Bool(true))
+                    && items.iter().enumerate().any(|(i, item)| items[..i].contains(item)) {
+                    violations.push(violation(path, "items must be unique".to_string()));
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.validate_at(item_schema, item, &child(path, &i.to_string()), violations);
+                    }
+                }
+            }
+            Value::String(text) => {
+                let length = text.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if length < min {
+                        violations.push(violation(path, format!("must be at least {} characters long", min)));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if length > max {
+                        violations.push(violation(path, format!("must be at most {} characters long", max)));
+                    }
+                }
+                if let Some(regex) = schema.get("pattern").and_then(Value::as_str).and_then(|pattern| self.patterns.get(pattern)) {
+                    if text.len() > MAX_PATTERN_INPUT {
+                        violations.push(violation(path, format!("is too long to match pattern {}, at most {} bytes", regex, MAX_PATTERN_INPUT)));
+                    } else if !regex.is_match(text) {
+                        violations.push(violation(path, format!("must match pattern {}", regex)));
+                    }
+                }
+            }
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|&min| number < min) {
+                    violations.push(violation(path, format!("must be >= {}", min)));
+                }
+                if let Some(max) = bound("maximum").filter(|&max| number > max) {
+                    violations.push(violation(path, format!("must be <= {}", max)));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|&min| number <= min) {
+                    violations.push(violation(path, format!("must be > {}", min)));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|&max| number >= max) {
+                    violations.push(violation(path, format!("must be < {}", max)));
+                }
+                if let Some(step) = bound("multipleOf").filter(|&step| step > 0.0 && (number / step).fract() != 0.0) {
+                    violations.push(violation(path, format!("must be a multiple of {}", step)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_patterns(schema: &Value, patterns: &mut HashMap<String, Regex>) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(Value::String(pattern)) = object.get("pattern") {
+                match Regex::new(pattern) {
+                    Ok(regex) => {
+                        patterns.insert(pattern.clone(), regex);
+                    }
+                    //the schema is the server's mistake, not the caller's
+                    Err(e) => log::warn!("Skipping pattern {}: {}", pattern, e),
+                }
+            }
+            object.values().for_each(|value| collect_patterns(value, patterns));
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_patterns(value, patterns)),
+        _ => {}
+    }
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "integer" => instance.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "number" => instance.is_number(),
+        other => type_name(instance) == other,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn child(path: &str, name: &str) -> String {
+    format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"))
+}
+
+fn violation(path: &str, message: String) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "pattern": "^[a-z]+$", "maxLength": 8},
+                "count": {"type": "integer", "minimum": 1, "maximum": 10},
+                "mode": {"enum": ["fast", "slow"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+                "owner": {
+                    "type": "object",
+                    "properties": {"id": {"type": "integer"}},
+                    "required": ["id"],
+                    "additionalProperties": false,
+                },
+            },
+            "required": ["name", "count"],
+        });
+        let validator = SchemaValidator::new(schema);
+
+        let valid = json!({"name": "report", "count": 3.0, "mode": "fast", "tags": ["a"], "owner": {"id": 7}});
+        assert!(validator.validate(&valid).is_empty());
+
+        let invalid = json!({
+            "name": "Report",
+            "mode": "turbo",
+            "tags": ["a", 2, "c"],
+            "owner": {"role": "admin"},
+        });
+        let violations = validator.validate(&invalid)
+            .into_iter()
+            .map(|violation| (violation.path, violation.message))
+            .collect::<Vec<_>>();
+        assert_eq!(violations, vec![
+            ("/count".to_string(), "is required".to_string()),
+            ("/mode".to_string(), r#"must be one of ["fast","slow"]"#.to_string()),
+            ("/name".to_string(), "must match pattern ^[a-z]+$".to_string()),
+            ("/owner/id".to_string(), "is required".to_string()),
+            ("/owner/role".to_string(), "is not an allowed property".to_string()),
+            ("/tags".to_string(), "must have at most 2 items".to_string()),
+            ("/tags/1".to_string(), "expected string, got number".to_string()),
+        ]);
+
+        assert_eq!(validator.validate(&json!([])), vec![SchemaViolation {
+            path: "".to_string(),
+            message: "expected object, got array".to_string(),
+        }]);
+        let minimum = SchemaValidator::new(json!({"properties": {"count": {"type": "integer", "minimum": 1}}}));
+        assert_eq!(minimum.validate(&json!({"count": 0}))[0].message, "must be >= 1");
+    }
+
+    #[test]
+    fn test_pattern_on_hostile_input() {
+        let validator = SchemaValidator::new(json!({
+            "properties": {
+                "word": {"type": "string", "pattern": "^[a-z]+$"},
+                "nested": {"type": "string", "pattern": "^(a+)+$"},
+            },
+        }));
+
+        //long inputs are matched without deep recursion, over the cap they are refused
+        let long = "a".repeat(MAX_PATTERN_INPUT);
+        assert!(validator.validate(&json!({"word": long})).is_empty());
+        let too_long = "a".repeat(100_000);
+        let violations = validator.validate(&json!({"word": too_long}));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].message.starts_with("is too long to match pattern"));
+
+        //nested quantifiers do not backtrack exponentially
+        let start = std::time::Instant::now();
+        let violations = validator.validate(&json!({"nested": format!("{}b", "a".repeat(4096))}));
+        assert_eq!(violations[0].message, "must match pattern ^(a+)+$");
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_skipped() {
+        let validator = SchemaValidator::new(json!({"properties": {"id": {"type": "string", "pattern": "(?=lookahead)"}}}));
+        assert!(validator.validate(&json!({"id": "anything"})).is_empty());
+    }
+}
@@ -38,6 +38,20 @@ impl Appender for ConsoleAppender {
     }
 }
 
+/// Console appender that never touches stdout, for transports that own it.
+#[derive(Debug, Clone)]
+pub struct StderrAppender;
+impl Appender for StderrAppender {
+    fn append(&self, record: &Record) {
+        eprintln!(
+            "[{}] {} -> {}",
+            record.level(),
+            record.metadata().target(),
+            record.args()
+        );
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileAppender {
     file: Arc<Mutex<std::fs::File>>,
@@ -81,8 +95,24 @@ impl McpInterceptorLogger {
         appenders.push(Arc::new(FileAppender::new("log/requests.log")));
         let logger = McpInterceptorLogger::new(appenders, LevelFilter::Info);
 
-        log::set_boxed_logger(Box::new(logger)).unwrap();
-        log::set_max_level(log::LevelFilter::Info);
+        //keep the first logger when initialized more than once
+        if log::set_boxed_logger(Box::new(logger)).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+    }
+
+    /// Same as `init` but logs to stderr, so stdout stays free for protocol traffic.
+    pub fn init_stderr()  {
+        let appenders: Vec<Arc<dyn Appender>> = vec![
+            Arc::new(StderrAppender),
+            Arc::new(FileAppender::new("log/requests.log")),
+        ];
+        let logger = McpInterceptorLogger::new(appenders, LevelFilter::Info);
+
+        //keep the first logger when initialized more than once
+        if log::set_boxed_logger(Box::new(logger)).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
     }
 
     pub fn set_level(l: log::LevelFilter){
@@ -23,4 +23,7 @@ pub mod shared_memory;
 pub mod logging;
 pub mod sessons;
 pub mod jobman;
+pub mod uri_template;
+pub mod tool_input;
+pub mod json_schema;
 pub use control_bus::ControlBus;
\ No newline at end of file
@@ -96,9 +96,8 @@ impl SessionStore {
 }
 
 
-thread_local! {
-    static SESSION_ID: RefCell<String> = RefCell::new("local".to_string());
-}
+//coroutine local so tool handlers keep their session across yields
+may::coroutine_local!(static SESSION_ID: RefCell<String> = RefCell::new("local".to_string()));
 
 
 
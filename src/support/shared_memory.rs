@@ -1,20 +1,34 @@
 #![allow(dead_code)]
 use std::fs::{File, OpenOptions};
-use std::io;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
+use std::sync::atomic::{AtomicUsize, AtomicBool, AtomicU32, Ordering};
 use memmap2::MmapMut;
-use std::mem::{size_of, align_of};
+use std::mem::{size_of, align_of, offset_of};
 use std::os::unix::fs::OpenOptionsExt;
 use thiserror::Error;
 use std::time::{Duration, Instant};
 use std::ptr::{self, NonNull};
+use std::sync::Mutex;
 
 const SHARED_MEM_MAGIC: u32 = 0xDEADBEEF;
 const DEFAULT_ALIGNMENT: usize = 64;
 
 const VERBOSE: bool = false;
 
+/// Empty polls before a reader parks on the futex.
+const SPIN_LIMIT: u32 = 64;
+
+/// Message records are prefixed with a u32 holding the chunk length and
+/// these flags, large messages travel as a run of chunks.
+const RECORD_HEADER: usize = 4;
+const RECORD_MORE: u32 = 1 << 31;
+const RECORD_CONTINUATION: u32 = 1 << 30;
+const RECORD_LEN_MASK: u32 = RECORD_CONTINUATION - 1;
+
+/// Longest a reader or writer stays parked before checking on its peer.
+const LIVENESS_INTERVAL: Duration = Duration::from_millis(100);
+
 #[repr(C, align(64))]
 struct SharedHeader {
     magic: u32,
@@ -22,8 +36,76 @@ struct SharedHeader {
     read_pos: AtomicUsize,
     write_pos: AtomicUsize,
     capacity: AtomicUsize,
+    /// Futex word, bumped by the writer after every publish.
+    seq: AtomicU32,
+    /// Readers parked on `seq`, lets the writer skip the wake syscall.
+    waiters: AtomicU32,
+    /// Futex word, bumped by the reader after every consume.
+    read_seq: AtomicU32,
+    /// Writers parked on `read_seq` waiting for space.
+    write_waiters: AtomicU32,
+    /// Set by whichever side lets go of the ring.
+    closed: AtomicBool,
+    creator_pid: AtomicU32,
+    /// Process that opened the ring, 0 until a peer attaches.
+    opener_pid: AtomicU32,
+    /// Bumped whenever the ring is recreated at the same path.
+    generation: AtomicU32,
+}
+
+/// Whether `pid` still exists, 0 standing for a peer that has not attached yet.
+fn process_alive(pid: u32) -> bool {
+    if pid == 0 || pid == std::process::id() {
+        return true;
+    }
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
 }
 
+/// Generation recorded in the ring file at `path`, 0 when there is none.
+fn existing_generation(path: &Path) -> u32 {
+    let mut buf = [0u8; size_of::<SharedHeader>()];
+    if File::open(path).and_then(|mut f| f.read_exact(&mut buf)).is_err() {
+        return 0;
+    }
+    let field = |offset: usize| u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap());
+    if field(offset_of!(SharedHeader, magic)) != SHARED_MEM_MAGIC {
+        return 0;
+    }
+    field(offset_of!(SharedHeader, generation))
+}
+
+/// Block while `word` still holds `expected`. The header lives in a shared
+/// file mapping, so the process-shared (non private) futex ops are used.
+#[cfg(target_os = "linux")]
+fn futex_wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    let ts = timeout.map(|t| libc::timespec {
+        tv_sec: t.as_secs() as libc::time_t,
+        tv_nsec: t.subsec_nanos() as libc::c_long,
+    });
+    let ts_ptr = ts.as_ref().map_or(ptr::null(), |t| t as *const libc::timespec);
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAIT, expected, ts_ptr, ptr::null::<u32>(), 0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn futex_wake(word: &AtomicU32) {
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAKE, i32::MAX, ptr::null::<libc::timespec>(), ptr::null::<u32>(), 0);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn futex_wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    if word.load(Ordering::Acquire) == expected {
+        std::thread::sleep(timeout.unwrap_or(Duration::from_millis(1)).min(Duration::from_millis(1)));
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn futex_wake(_word: &AtomicU32) {}
+
 #[derive(Error, Debug)]
 pub enum SharedMemoryError {
     #[error("IO error: {0}")]
@@ -40,6 +122,12 @@ pub enum SharedMemoryError {
     BufferOverflow,
     #[error("Alignment error")]
     AlignmentError,
+    #[error("Peer process is gone")]
+    PeerGone,
+    #[error("No free registration slot")]
+    NoFreeSlot,
+    #[error("Registration rejected by the server")]
+    Rejected,
 }
 
 fn align_up(size: usize, align: usize) -> usize {
@@ -53,6 +141,10 @@ pub struct SharedMemory {
     header: NonNull<SharedHeader>,
     data_ptr: NonNull<u8>,
     is_creator: bool,
+    /// Generation of the ring this instance is attached to.
+    generation: u32,
+    /// Chunks of a message whose remainder has not arrived yet.
+    partial: Mutex<Option<Vec<u8>>>,
 }
 
 impl SharedMemory {
@@ -74,6 +166,14 @@ impl SharedMemory {
             )));
         }
 
+        //never truncate a ring a peer may still have mapped, unlink it so
+        //that peer keeps the old pages and sees the owner gone
+        let generation = existing_generation(path.as_ref()).wrapping_add(1);
+        match std::fs::remove_file(path.as_ref()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
         let file = OpenOptions::new()
             .read(true)
             .write(true)
@@ -103,6 +203,14 @@ impl SharedMemory {
                 read_pos: AtomicUsize::new(0),
                 write_pos: AtomicUsize::new(0),
                 capacity: AtomicUsize::new(initial_size),
+                seq: AtomicU32::new(0),
+                waiters: AtomicU32::new(0),
+                read_seq: AtomicU32::new(0),
+                write_waiters: AtomicU32::new(0),
+                closed: AtomicBool::new(false),
+                creator_pid: AtomicU32::new(std::process::id()),
+                opener_pid: AtomicU32::new(0),
+                generation: AtomicU32::new(generation),
             });
         }
 
@@ -115,6 +223,8 @@ impl SharedMemory {
             header: NonNull::new(header_ptr).unwrap(),
             data_ptr: NonNull::new(data_ptr).unwrap(),
             is_creator: true,
+            generation,
+            partial: Mutex::new(None),
         })
     }
 
@@ -125,6 +235,10 @@ impl SharedMemory {
             .write(true)
             .open(path)?;
 
+        if file.metadata()?.len() < size_of::<SharedHeader>() as u64 {
+            return Err(SharedMemoryError::Corrupted);
+        }
+
         let mut mmap = unsafe { MmapMut::map_mut(&file)? };
         let header_ptr = mmap.as_mut_ptr() as *mut SharedHeader;
 
@@ -143,6 +257,13 @@ impl SharedMemory {
             return Err(SharedMemoryError::Corrupted);
         }
 
+        let header = unsafe { &*header_ptr };
+        if header.closed.load(Ordering::Acquire) {
+            return Err(SharedMemoryError::PeerGone);
+        }
+        header.opener_pid.store(std::process::id(), Ordering::Release);
+        let generation = header.generation.load(Ordering::Acquire);
+
         Ok(Self {
             mmap,
             file,
@@ -150,6 +271,8 @@ impl SharedMemory {
             header: NonNull::new(header_ptr).unwrap(),
             data_ptr: NonNull::new(data_ptr).unwrap(),
             is_creator: false,
+            generation,
+            partial: Mutex::new(None),
         })
     }
 
@@ -157,6 +280,10 @@ impl SharedMemory {
         let header = unsafe { self.header.as_ref() };
         let capacity = header.capacity.load(Ordering::SeqCst);
 
+        if header.closed.load(Ordering::Acquire) {
+            return Err(SharedMemoryError::PeerGone);
+        }
+
         if data.len() > capacity {
             return Err(SharedMemoryError::DataTooLarge(capacity, data.len()));
         }
@@ -214,10 +341,150 @@ impl SharedMemory {
             if VERBOSE {
                 println!("@writing pos  {}", write_pos + data.len());
             }
+        }
+
+        //publish, then wake parked readers; no msync, the mapping is shared
+        header.seq.fetch_add(1, Ordering::SeqCst);
+        if header.waiters.load(Ordering::SeqCst) > 0 {
+            futex_wake(&header.seq);
+        }
+
+        Ok(())
+    }
+
+    /// Like `write`, but waits up to `timeout` for the reader to make room
+    /// instead of failing with `BufferOverflow`.
+    pub fn write_timeout(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), SharedMemoryError> {
+        let start = Instant::now();
+        let header = unsafe { self.header.as_ref() };
+        let mut spins = 0;
+
+        loop {
+            //read the futex word first, a consume after this point changes it
+            let read_seq = header.read_seq.load(Ordering::SeqCst);
+            match self.write(data) {
+                Err(SharedMemoryError::BufferOverflow) => {}
+                result => return result,
+            }
+
+            let remaining = match timeout {
+                Some(timeout) => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= timeout {
+                        return Err(SharedMemoryError::Timeout);
+                    }
+                    Some(timeout - elapsed)
+                }
+                None => None,
+            };
+
+            if spins < SPIN_LIMIT {
+                spins += 1;
+                std::hint::spin_loop();
+                continue;
+            }
+
+            //a dead reader never makes room
+            self.check_peer()?;
+            header.write_waiters.fetch_add(1, Ordering::SeqCst);
+            futex_wait(&header.read_seq, read_seq, Some(remaining.map_or(LIVENESS_INTERVAL, |r| r.min(LIVENESS_INTERVAL))));
+            header.write_waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Largest chunk a message is split into, a quarter of the ring so the
+    /// reader can drain one chunk while the next is written.
+    fn max_chunk(&self) -> usize {
+        (self.capacity() / 4).saturating_sub(RECORD_HEADER).clamp(1, RECORD_LEN_MASK as usize)
+    }
+
+    /// Publish `data` as one message. Each chunk becomes visible to the
+    /// reader as a whole, and the reader hands out only complete messages.
+    /// Waits up to `timeout` for room; a message cut short by the timeout is
+    /// dropped by the reader when the next one starts.
+    pub fn write_message(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), SharedMemoryError> {
+        let start = Instant::now();
+        let max_chunk = self.max_chunk();
+        let mut chunks = data.chunks(max_chunk).peekable();
+        let mut record = Vec::with_capacity(RECORD_HEADER + max_chunk.min(data.len()));
+        let mut flags = 0;
+
+        //an empty message still needs its record
+        if chunks.peek().is_none() {
+            return self.write_timeout(&0u32.to_be_bytes(), timeout);
+        }
+
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() {
+                flags |= RECORD_MORE;
+            } else {
+                flags &= !RECORD_MORE;
+            }
+            record.clear();
+            record.extend_from_slice(&(chunk.len() as u32 | flags).to_be_bytes());
+            record.extend_from_slice(chunk);
 
-            self.mmap.flush()?;
+            let remaining = match timeout {
+                Some(timeout) => Some(timeout.checked_sub(start.elapsed()).ok_or(SharedMemoryError::Timeout)?),
+                None => None,
+            };
+            self.write_timeout(&record, remaining)?;
+            flags |= RECORD_CONTINUATION;
         }
+        Ok(())
+    }
 
+    /// Receive the next complete message, waiting up to `timeout`. Chunks
+    /// that arrived before a timeout are kept for the next call.
+    pub fn read_message(&self, timeout: Option<Duration>) -> Result<Vec<u8>, SharedMemoryError> {
+        let start = Instant::now();
+        let mut partial = self.partial.lock()
+            .map_err(|_| SharedMemoryError::Corrupted)?;
+
+        loop {
+            let remaining = match timeout {
+                Some(timeout) => Some(timeout.checked_sub(start.elapsed()).ok_or(SharedMemoryError::Timeout)?),
+                None => None,
+            };
+
+            let mut record_header = [0u8; RECORD_HEADER];
+            self.read_exact_timeout(&mut record_header, remaining)?;
+            let record_header = u32::from_be_bytes(record_header);
+            let len = (record_header & RECORD_LEN_MASK) as usize;
+            if len > self.capacity() {
+                return Err(SharedMemoryError::Corrupted);
+            }
+
+            //records are published whole, the chunk is already in the ring
+            let mut chunk = vec![0u8; len];
+            self.read_exact_timeout(&mut chunk, None)?;
+
+            let mut message = match (partial.take(), record_header & RECORD_CONTINUATION != 0) {
+                (Some(mut message), true) => {
+                    message.extend_from_slice(&chunk);
+                    message
+                }
+                //the tail of an abandoned message
+                (None, true) => continue,
+                //a new message, drop whatever the writer gave up on
+                (_, false) => chunk,
+            };
+
+            if record_header & RECORD_MORE == 0 {
+                return Ok(message);
+            }
+            message.reserve(len);
+            *partial = Some(message);
+        }
+    }
+
+    fn read_exact_timeout(&self, mut buf: &mut [u8], timeout: Option<Duration>) -> Result<(), SharedMemoryError> {
+        let start = Instant::now();
+        while !buf.is_empty() {
+            let remaining = timeout.map(|timeout| timeout.saturating_sub(start.elapsed()));
+            let n = self.read_timeout(buf, remaining)?;
+            buf = &mut buf[n..];
+        }
         Ok(())
     }
 
@@ -232,9 +499,11 @@ impl SharedMemory {
     ) -> Result<usize, SharedMemoryError> {
         let start = Instant::now();
         let header = unsafe { self.header.as_ref() };
-        let mut sleep_duration = Duration::from_micros(100);
+        let mut spins = 0;
 
         loop {
+            //read the futex word first, a publish after this point changes it
+            let seq = header.seq.load(Ordering::SeqCst);
             let write_pos = header.write_pos.load(Ordering::Acquire);
             let read_pos = header.read_pos.load(Ordering::Acquire);
 
@@ -282,23 +551,40 @@ impl SharedMemory {
                     println!("@reading pos  {}", read_pos + to_read);
                 }
 
+                header.read_seq.fetch_add(1, Ordering::SeqCst);
+                if header.write_waiters.load(Ordering::SeqCst) > 0 {
+                    futex_wake(&header.read_seq);
+                }
+
                 if write_pos == read_pos + to_read {
                     header.ready.store(false, Ordering::SeqCst);
                 }
 
-                self.mmap.flush()?;
                 return Ok(to_read);
             }
 
-            if let Some(timeout) = timeout {
-                if start.elapsed() >= timeout {
-                    return Err(SharedMemoryError::Timeout);
+            let remaining = match timeout {
+                Some(timeout) => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= timeout {
+                        return Err(SharedMemoryError::Timeout);
+                    }
+                    Some(timeout - elapsed)
                 }
-                sleep_duration = sleep_duration.min(timeout - start.elapsed());
+                None => None,
+            };
+
+            if spins < SPIN_LIMIT {
+                spins += 1;
+                std::hint::spin_loop();
+                continue;
             }
 
-            std::thread::sleep(sleep_duration);
-            sleep_duration = sleep_duration.saturating_mul(2).min(Duration::from_millis(10));
+            //whatever the peer wrote before going away has been drained
+            self.check_peer()?;
+            header.waiters.fetch_add(1, Ordering::SeqCst);
+            futex_wait(&header.seq, seq, Some(remaining.map_or(LIVENESS_INTERVAL, |r| r.min(LIVENESS_INTERVAL))));
+            header.waiters.fetch_sub(1, Ordering::SeqCst);
         }
     }
 
@@ -325,6 +611,36 @@ impl SharedMemory {
         write_pos - read_pos
     }
 
+    /// Generation of the ring this instance is attached to.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Mark the ring closed. The peer still reads whatever is left in it and
+    /// gets `PeerGone` after that, writes fail right away.
+    pub fn close(&self) {
+        let header = unsafe { self.header.as_ref() };
+        header.closed.store(true, Ordering::Release);
+        header.seq.fetch_add(1, Ordering::SeqCst);
+        header.read_seq.fetch_add(1, Ordering::SeqCst);
+        futex_wake(&header.seq);
+        futex_wake(&header.read_seq);
+    }
+
+    /// `PeerGone` once the other side closed the ring or its process died.
+    fn check_peer(&self) -> Result<(), SharedMemoryError> {
+        let header = unsafe { self.header.as_ref() };
+        let peer = if self.is_creator {
+            header.opener_pid.load(Ordering::Acquire)
+        } else {
+            header.creator_pid.load(Ordering::Acquire)
+        };
+        if header.closed.load(Ordering::Acquire) || !process_alive(peer) {
+            return Err(SharedMemoryError::PeerGone);
+        }
+        Ok(())
+    }
+
     pub fn check_health(&self) -> Result<(), SharedMemoryError> {
         let header = unsafe { self.header.as_ref() };
         if header.magic != SHARED_MEM_MAGIC {
@@ -334,19 +650,52 @@ impl SharedMemory {
         if cap == 0 || cap % DEFAULT_ALIGNMENT != 0 {
             return Err(SharedMemoryError::Corrupted);
         }
+        self.check_peer()?;
+        //a restarted creator put a new ring at our path
+        if !self.is_creator && existing_generation(&self.path) != self.generation {
+            return Err(SharedMemoryError::PeerGone);
+        }
         Ok(())
     }
 
+    /// Get back to a usable ring after the peer went away. The creator drops
+    /// whatever is left and waits for a new peer, an opener reattaches to the
+    /// ring a restarted creator put at the same path.
     pub fn recover(&mut self) -> Result<(), SharedMemoryError> {
-        self.check_health()?;
-        let header = unsafe { self.header.as_ref() };
-        header.ready.store(false, Ordering::SeqCst);
-        Ok(())
+        match self.check_health() {
+            Err(SharedMemoryError::PeerGone) if self.is_creator => {
+                let header = unsafe { self.header.as_ref() };
+                header.read_pos.store(header.write_pos.load(Ordering::Acquire), Ordering::Release);
+                header.opener_pid.store(0, Ordering::Release);
+                header.closed.store(false, Ordering::Release);
+                header.ready.store(false, Ordering::SeqCst);
+                if let Ok(mut partial) = self.partial.lock() {
+                    *partial = None;
+                }
+                Ok(())
+            }
+            Err(SharedMemoryError::PeerGone) => {
+                *self = Self::open(&self.path)?;
+                self.check_health()
+            }
+            result => {
+                let header = unsafe { self.header.as_ref() };
+                header.ready.store(false, Ordering::SeqCst);
+                result
+            }
+        }
     }
 }
 
 impl Drop for SharedMemory {
     fn drop(&mut self) {
+        let header = unsafe { self.header.as_ref() };
+        if self.is_creator {
+            //tell the peer right away instead of leaving it to the pid check
+            self.close();
+        } else {
+            let _ = header.opener_pid.compare_exchange(std::process::id(), 0, Ordering::AcqRel, Ordering::Relaxed);
+        }
         if self.is_creator {
             if let Err(e) = std::fs::remove_file(&self.path) {
                 if cfg!(debug_assertions) {
@@ -397,8 +746,217 @@ impl MemoryDuplex {
      pub fn write(&self, data: &[u8]) -> Result<(), SharedMemoryError> {
         self.writer.write(data)
     }
+
+    pub fn read_timeout(&self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize, SharedMemoryError> {
+        self.reader.read_timeout(buf, timeout)
+    }
+
+    pub fn write_message(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), SharedMemoryError> {
+        self.writer.write_message(data, timeout)
+    }
+
+    pub fn read_message(&self, timeout: Option<Duration>) -> Result<Vec<u8>, SharedMemoryError> {
+        self.reader.read_message(timeout)
+    }
+
+    pub fn check_health(&self) -> Result<(), SharedMemoryError> {
+        self.reader.check_health()?;
+        self.writer.check_health()
+    }
+
+    /// See `SharedMemory::recover`, applied to both rings.
+    pub fn recover(&mut self) -> Result<(), SharedMemoryError> {
+        self.reader.recover()?;
+        self.writer.recover()
+    }
+
+    /// Split into the (reader, writer) rings so each side can be driven
+    /// from its own thread.
+    pub fn into_parts(self) -> (SharedMemory, SharedMemory) {
+        (self.reader, self.writer)
+    }
 }
 
+const CONTROL_MAGIC: u32 = 0x4D435053;
+const CONTROL_SLOTS: usize = 64;
+const CLIENT_ID_LEN: usize = 32;
+
+const SLOT_FREE: u32 = 0;
+const SLOT_CLAIMED: u32 = 1;
+const SLOT_REQUESTED: u32 = 2;
+const SLOT_ACCEPTED: u32 = 3;
+const SLOT_REJECTED: u32 = 4;
+
+#[repr(C)]
+struct ControlSlot {
+    state: AtomicU32,
+    id: [u8; CLIENT_ID_LEN],
+}
+
+#[repr(C, align(64))]
+struct ControlHeader {
+    magic: u32,
+    /// Futex word, bumped whenever a client files a request.
+    seq: AtomicU32,
+    slots: [ControlSlot; CONTROL_SLOTS],
+}
+
+/// Small registration table through which clients ask a shared memory
+/// server for a dedicated ring pair. Clients claim a free slot with a CAS,
+/// so any number of them can register concurrently.
+pub struct ControlSegment {
+    mmap: MmapMut,
+    path: PathBuf,
+    header: NonNull<ControlHeader>,
+    is_creator: bool,
+}
+
+impl ControlSegment {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, SharedMemoryError> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        //unlink instead of truncating, a stale client may still have it mapped
+        match std::fs::remove_file(path.as_ref()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o660)
+            .open(path.as_ref())?;
+        file.set_len(align_up(size_of::<ControlHeader>(), 4096) as u64)?;
+
+        //a fresh file is zero filled, so every slot starts out free
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        let header_ptr = mmap.as_mut_ptr() as *mut ControlHeader;
+        unsafe {
+            ptr::addr_of_mut!((*header_ptr).magic).write(CONTROL_MAGIC);
+        }
+
+        Ok(Self {
+            mmap,
+            path: path.as_ref().to_path_buf(),
+            header: NonNull::new(header_ptr).unwrap(),
+            is_creator: true,
+        })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SharedMemoryError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())?;
+        if file.metadata()?.len() < size_of::<ControlHeader>() as u64 {
+            return Err(SharedMemoryError::Corrupted);
+        }
+
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        let header_ptr = mmap.as_mut_ptr() as *mut ControlHeader;
+        if unsafe { (*header_ptr).magic } != CONTROL_MAGIC {
+            return Err(SharedMemoryError::Corrupted);
+        }
+
+        Ok(Self {
+            mmap,
+            path: path.as_ref().to_path_buf(),
+            header: NonNull::new(header_ptr).unwrap(),
+            is_creator: false,
+        })
+    }
+
+    /// Client side: file `id` with the server and wait until it is accepted.
+    pub fn register(&self, id: &str, timeout: Duration) -> Result<(), SharedMemoryError> {
+        if id.len() > CLIENT_ID_LEN {
+            return Err(SharedMemoryError::DataTooLarge(CLIENT_ID_LEN, id.len()));
+        }
+        let header = unsafe { self.header.as_ref() };
+
+        let index = (0..CONTROL_SLOTS)
+            .find(|&i| header.slots[i].state
+                .compare_exchange(SLOT_FREE, SLOT_CLAIMED, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok())
+            .ok_or(SharedMemoryError::NoFreeSlot)?;
+        let slot = &header.slots[index];
+
+        unsafe {
+            let id_ptr = ptr::addr_of_mut!((*self.header.as_ptr()).slots[index].id) as *mut u8;
+            ptr::write_bytes(id_ptr, 0, CLIENT_ID_LEN);
+            ptr::copy_nonoverlapping(id.as_ptr(), id_ptr, id.len());
+        }
+        slot.state.store(SLOT_REQUESTED, Ordering::Release);
+        header.seq.fetch_add(1, Ordering::SeqCst);
+        futex_wake(&header.seq);
+
+        //registration is rare, plain polling is good enough here
+        let start = Instant::now();
+        loop {
+            match slot.state.load(Ordering::Acquire) {
+                SLOT_ACCEPTED => {
+                    slot.state.store(SLOT_FREE, Ordering::Release);
+                    return Ok(());
+                }
+                SLOT_REJECTED => {
+                    slot.state.store(SLOT_FREE, Ordering::Release);
+                    return Err(SharedMemoryError::Rejected);
+                }
+                _ => {}
+            }
+            if start.elapsed() >= timeout {
+                //withdraw unless the server picked it up meanwhile
+                if slot.state.compare_exchange(SLOT_REQUESTED, SLOT_FREE, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                    return Err(SharedMemoryError::Timeout);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Server side: hand every pending request to `accept` and report the
+    /// outcome back to the client. Waits up to `timeout` when none is pending.
+    pub fn accept(&self, timeout: Option<Duration>, mut accept: impl FnMut(&str) -> bool) -> usize {
+        let header = unsafe { self.header.as_ref() };
+        let seq = header.seq.load(Ordering::SeqCst);
+
+        let mut handled = 0;
+        for (index, slot) in header.slots.iter().enumerate() {
+            if slot.state.load(Ordering::Acquire) != SLOT_REQUESTED {
+                continue;
+            }
+            let id = unsafe {
+                let id_ptr = ptr::addr_of!((*self.header.as_ptr()).slots[index].id) as *const u8;
+                let bytes = std::slice::from_raw_parts(id_ptr, CLIENT_ID_LEN);
+                let len = bytes.iter().position(|b| *b == 0).unwrap_or(CLIENT_ID_LEN);
+                String::from_utf8_lossy(&bytes[..len]).to_string()
+            };
+            let state = if accept(&id) { SLOT_ACCEPTED } else { SLOT_REJECTED };
+            //the client may have given up in the meantime
+            let _ = slot.state.compare_exchange(SLOT_REQUESTED, state, Ordering::AcqRel, Ordering::Relaxed);
+            handled += 1;
+        }
+
+        if handled == 0 {
+            futex_wait(&header.seq, seq, timeout);
+        }
+        handled
+    }
+}
+
+impl Drop for ControlSegment {
+    fn drop(&mut self) {
+        if self.is_creator {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+unsafe impl Send for ControlSegment {}
+
 
 #[cfg(test)]
 mod tests {
@@ -446,4 +1004,160 @@ mod tests {
         rmem.read(&mut rbuf).unwrap();
         assert_eq!(&buf[144..192], rbuf);
     }
+
+    #[test]
+    fn test_shared_memory_messages() {
+        let mem = SharedMemory::create("test5", 256).unwrap();
+        let rmem = SharedMemory::open("test5").unwrap();
+
+        //far larger than the ring, the writer waits for the reader
+        let large: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let expected = large.clone();
+        let reader = std::thread::spawn(move || {
+            let first = rmem.read_message(Some(Duration::from_secs(5))).unwrap();
+            let second = rmem.read_message(Some(Duration::from_secs(5))).unwrap();
+            (first, second, rmem)
+        });
+        mem.write_message(&large, Some(Duration::from_secs(5))).unwrap();
+        mem.write_message(b"", Some(Duration::from_secs(5))).unwrap();
+        let (first, second, rmem) = reader.join().unwrap();
+        assert_eq!(first, expected);
+        assert!(second.is_empty());
+
+        //nobody drains the ring, the writer gives up mid message
+        assert!(matches!(mem.write_message(&large, Some(Duration::from_millis(20))), Err(SharedMemoryError::Timeout)));
+        assert!(matches!(mem.write(&[0u8; 256]), Err(SharedMemoryError::BufferOverflow)));
+        let reader = std::thread::spawn(move || {
+            let message = rmem.read_message(Some(Duration::from_secs(5))).unwrap();
+            (message, rmem)
+        });
+        mem.write_message(b"next", Some(Duration::from_secs(5))).unwrap();
+        let (message, rmem) = reader.join().unwrap();
+        assert_eq!(message, b"next");
+        assert!(matches!(rmem.read_message(Some(Duration::from_millis(20))), Err(SharedMemoryError::Timeout)));
+    }
+
+    #[test]
+    fn test_shared_memory_peer_gone() {
+        let server = SharedMemory::create("test6", 1024).unwrap();
+        let mut client = SharedMemory::open("test6").unwrap();
+        let generation = client.generation();
+        server.write(b"bye").unwrap();
+        drop(server);
+
+        //pending data is still delivered, then the reader is told
+        let mut buf = vec![0u8; 3];
+        client.read(&mut buf).unwrap();
+        assert_eq!(buf, b"bye");
+        assert!(matches!(client.read(&mut buf), Err(SharedMemoryError::PeerGone)));
+        assert!(matches!(client.write(b"x"), Err(SharedMemoryError::PeerGone)));
+        assert!(matches!(client.check_health(), Err(SharedMemoryError::PeerGone)));
+
+        //reattach to a restarted server
+        let server = SharedMemory::create("test6", 1024).unwrap();
+        client.recover().unwrap();
+        server.write(b"hi!").unwrap();
+        client.read(&mut buf).unwrap();
+        assert_eq!(buf, b"hi!");
+
+        //a crashed server leaves its file behind with a dead owner
+        let mut dead = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = dead.id();
+        dead.wait().unwrap();
+        unsafe { server.header.as_ref() }.creator_pid.store(dead_pid, Ordering::Release);
+        let crashed_generation = server.generation();
+        std::mem::forget(server);
+        assert!(matches!(client.read_timeout(&mut buf, Some(Duration::from_secs(5))), Err(SharedMemoryError::PeerGone)));
+
+        let server = SharedMemory::create("test6", 1024).unwrap();
+        assert_eq!(server.generation(), crashed_generation + 1);
+        client.recover().unwrap();
+        assert_eq!(client.generation(), server.generation());
+        assert!(client.generation() > generation);
+        client.check_health().unwrap();
+
+        //a dead client is noticed by the server, which can take a new one
+        let mut server = server;
+        unsafe { server.header.as_ref() }.opener_pid.store(dead_pid, Ordering::Release);
+        assert!(matches!(server.read_timeout(&mut buf, Some(Duration::from_secs(5))), Err(SharedMemoryError::PeerGone)));
+        server.recover().unwrap();
+        server.check_health().unwrap();
+
+        //a client closing its end is seen once its last message is read,
+        //an empty message is just a message
+        let client = SharedMemory::open("test6").unwrap();
+        client.write_message(b"", None).unwrap();
+        client.write_message(b"bye", None).unwrap();
+        client.close();
+        assert!(matches!(client.write(b"x"), Err(SharedMemoryError::PeerGone)));
+        assert_eq!(server.read_message(None).unwrap(), b"");
+        assert_eq!(server.read_message(None).unwrap(), b"bye");
+        assert!(matches!(server.read_message(None), Err(SharedMemoryError::PeerGone)));
+        drop(client);
+        server.recover().unwrap();
+        let client = SharedMemory::open("test6").unwrap();
+        client.write(b"new").unwrap();
+        server.read(&mut buf).unwrap();
+        assert_eq!(buf, b"new");
+    }
+
+    #[test]
+    fn test_control_segment() {
+        let server = ControlSegment::create("test4").unwrap();
+        let client = ControlSegment::open("test4").unwrap();
+
+        let registered = std::thread::spawn(move || client.register("agent-1", Duration::from_secs(5)));
+        let mut accepted = Vec::new();
+        while accepted.is_empty() {
+            server.accept(Some(Duration::from_millis(100)), |id| {
+                accepted.push(id.to_string());
+                true
+            });
+        }
+        assert_eq!(accepted, vec!["agent-1".to_string()]);
+        registered.join().unwrap().unwrap();
+
+        //nobody answers, the request is withdrawn
+        let client = ControlSegment::open("test4").unwrap();
+        assert!(matches!(client.register("agent-2", Duration::from_millis(20)), Err(SharedMemoryError::Timeout)));
+        assert_eq!(server.accept(Some(Duration::from_millis(1)), |_| true), 0);
+
+        let client = ControlSegment::open("test4").unwrap();
+        let rejected = std::thread::spawn(move || client.register("agent-3", Duration::from_secs(5)));
+        while server.accept(Some(Duration::from_millis(100)), |_| false) == 0 {}
+        assert!(matches!(rejected.join().unwrap(), Err(SharedMemoryError::Rejected)));
+
+        //every slot taken by requests nobody answers
+        for slot in unsafe { server.header.as_ref() }.slots.iter() {
+            slot.state.store(SLOT_REQUESTED, Ordering::Release);
+        }
+        let client = ControlSegment::open("test4").unwrap();
+        assert!(matches!(client.register("agent-4", Duration::from_millis(20)), Err(SharedMemoryError::NoFreeSlot)));
+
+        //a crashed server leaves the file behind, recreating it must not
+        //wipe the mapping a stale client still holds
+        let stale = ControlSegment::open("test4").unwrap();
+        std::mem::forget(server);
+        let _server = ControlSegment::create("test4").unwrap();
+        assert_eq!(unsafe { stale.header.as_ref() }.slots[0].state.load(Ordering::Acquire), SLOT_REQUESTED);
+    }
+
+    #[test]
+    fn test_shared_memory_wakeup() {
+        let mem = SharedMemory::create("test3", 1024).unwrap();
+        let rmem = SharedMemory::open("test3").unwrap();
+        let mut buf = vec![0u8; 5];
+
+        assert!(matches!(rmem.read_timeout(&mut buf, Some(Duration::from_millis(20))), Err(SharedMemoryError::Timeout)));
+
+        //a parked reader is woken by the write
+        let reader = std::thread::spawn(move || {
+            let mut buf = vec![0u8; 5];
+            let n = rmem.read_timeout(&mut buf, Some(Duration::from_secs(5))).unwrap();
+            (n, buf)
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        mem.write(b"hello").unwrap();
+        assert_eq!(reader.join().unwrap(), (5, b"hello".to_vec()));
+    }
 }
\ No newline at end of file
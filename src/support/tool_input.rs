@@ -0,0 +1,219 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::schema::schema::ToolInputSchema;
+
+/// JSON Schema of the values a tool argument can take.
+pub trait SchemaType {
+    fn schema() -> Value;
+
+    /// Whether callers have to supply the argument.
+    fn required() -> bool {
+        true
+    }
+}
+
+/// Arguments of a tool, deserialized from tools/call and described to
+/// clients by the schema tools/list advertises. Implement it with `tool_input!`.
+pub trait ToolInput: DeserializeOwned {
+    fn input_schema() -> ToolInputSchema;
+}
+
+macro_rules! schema_type {
+    ($schema:expr => $($ty:ty),*) => {
+        $(impl SchemaType for $ty {
+            fn schema() -> Value {
+                $schema
+            }
+        })*
+    };
+}
+
+schema_type!(json!({"type": "string"}) => String, char);
+schema_type!(json!({"type": "boolean"}) => bool);
+schema_type!(json!({"type": "integer"}) => i8, i16, i32, i64, isize);
+schema_type!(json!({"type": "integer", "minimum": 0}) => u8, u16, u32, u64, usize);
+schema_type!(json!({"type": "number"}) => f32, f64);
+schema_type!(json!({}) => Value);
+
+impl<T: SchemaType> SchemaType for Option<T> {
+    fn schema() -> Value {
+        T::schema()
+    }
+
+    fn required() -> bool {
+        false
+    }
+}
+
+impl<T: SchemaType> SchemaType for Vec<T> {
+    fn schema() -> Value {
+        json!({"type": "array", "items": T::schema()})
+    }
+}
+
+impl<T: SchemaType> SchemaType for HashMap<String, T> {
+    fn schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::schema()})
+    }
+}
+
+/// One field of a `tool_input!` struct.
+pub struct FieldSchema {
+    name: &'static str,
+    schema: Value,
+    required: bool,
+}
+
+/// Schema of a field of type `T`, described by its doc comment lines.
+pub fn field<T: SchemaType>(name: &'static str, docs: &[&str]) -> FieldSchema {
+    let mut schema = T::schema();
+    let description = docs.iter().map(|line| line.trim()).collect::<Vec<_>>().join(" ");
+    if !description.is_empty() {
+        schema["description"] = Value::String(description);
+    }
+    FieldSchema {
+        name,
+        schema,
+        required: T::required(),
+    }
+}
+
+pub fn object_schema(fields: Vec<FieldSchema>) -> ToolInputSchema {
+    let properties = DashMap::new();
+    let mut required = Vec::new();
+    for field in fields {
+        if field.required {
+            required.push(field.name.to_string());
+        }
+        properties.insert(field.name.to_string(), field.schema);
+    }
+    ToolInputSchema {
+        r#type: "object".to_string(),
+        properties: Some(properties),
+        required: Some(required),
+    }
+}
+
+/// Schema of a `tool_input!` struct used as the type of another one's field.
+pub fn object_value(schema: ToolInputSchema) -> Value {
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+/// Declare a tool's argument struct along with its input schema. Fields
+/// become properties, their doc comments the descriptions, and every field
+/// that is not an `Option` is required. Needs `serde` in the calling crate.
+///
+/// ```ignore
+/// mcps::tool_input! {
+///     pub struct AddArgs {
+///         /// First operand
+///         pub a: i64,
+///         pub b: Option<i64>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! tool_input {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        $vis struct $name {
+            $(
+                $(#[doc = $doc])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::support::tool_input::ToolInput for $name {
+            fn input_schema() -> $crate::schema::schema::ToolInputSchema {
+                $crate::support::tool_input::object_schema(vec![
+                    $($crate::support::tool_input::field::<$ty>(stringify!($field), &[$($doc),*]),)*
+                ])
+            }
+        }
+
+        impl $crate::support::tool_input::SchemaType for $name {
+            fn schema() -> serde_json::Value {
+                $crate::support::tool_input::object_value(
+                    <$name as $crate::support::tool_input::ToolInput>::input_schema()
+                )
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::tool_input! {
+        /// Where to search.
+        pub struct Scope {
+            pub path: String,
+        }
+    }
+
+    crate::tool_input! {
+        pub struct SearchArgs {
+            /// Text to look for,
+            /// matched case insensitively
+            pub query: String,
+            pub limit: Option<u32>,
+            pub tags: Vec<String>,
+            pub scope: Option<Scope>,
+        }
+    }
+
+    #[test]
+    fn test_tool_input() {
+        let schema = SearchArgs::input_schema();
+        assert_eq!(schema.r#type, "object");
+        assert_eq!(schema.required, Some(vec!["query".to_string(), "tags".to_string()]));
+
+        let properties = schema.properties.unwrap();
+        assert_eq!(*properties.get("query").unwrap(), json!({
+            "type": "string",
+            "description": "Text to look for, matched case insensitively",
+        }));
+        assert_eq!(*properties.get("limit").unwrap(), json!({"type": "integer", "minimum": 0}));
+        assert_eq!(*properties.get("tags").unwrap(), json!({"type": "array", "items": {"type": "string"}}));
+        assert_eq!(*properties.get("scope").unwrap(), json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"],
+        }));
+
+        let args: SearchArgs = serde_json::from_value(json!({"query": "todo", "tags": []})).unwrap();
+        assert_eq!(args.query, "todo");
+        assert!(args.limit.is_none());
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+
+use crate::MCPError;
+
+enum Part {
+    Literal(String),
+    /// `{name}` stops at `/`, `{+name}` and `{#name}` may span it.
+    Variable { name: String, reserved: bool },
+}
+
+/// RFC 6570 URI template limited to simple and reserved expansion, enough to
+/// route `resources/read` requests to the template that produced the URI.
+pub struct UriTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<Self, MCPError> {
+        let invalid = |reason: &str| MCPError::Protocol(format!("Invalid URI template {}: {}", template, reason));
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| invalid("unclosed expression"))? + open;
+            let expression = &rest[open + 1..close];
+            let (name, reserved) = match expression.chars().next() {
+                Some('+') | Some('#') => (&expression[1..], true),
+                Some(c) if c.is_alphanumeric() || c == '_' => (expression, false),
+                _ => return Err(invalid("unsupported expression")),
+            };
+            if name.is_empty() || name.contains(',') {
+                return Err(invalid("unsupported expression"));
+            }
+            parts.push(Part::Variable {
+                name: name.to_string(),
+                reserved,
+            });
+            rest = &rest[close + 1..];
+        }
+        if rest.contains('}') {
+            return Err(invalid("unbalanced braces"));
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(UriTemplate {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Variable values when `uri` is an expansion of this template.
+    pub fn matches(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut variables = HashMap::new();
+        if match_parts(&self.parts, uri, &mut variables) {
+            Some(variables)
+        } else {
+            None
+        }
+    }
+}
+
+fn match_parts(parts: &[Part], uri: &str, variables: &mut HashMap<String, String>) -> bool {
+    match parts.split_first() {
+        None => uri.is_empty(),
+        Some((Part::Literal(literal), rest)) => uri.strip_prefix(literal.as_str())
+            .is_some_and(|uri| match_parts(rest, uri, variables)),
+        Some((Part::Variable { name, reserved }, rest)) => {
+            //shortest value first, backtrack when the remainder does not fit
+            for end in (1..=uri.len()).filter(|end| uri.is_char_boundary(*end)) {
+                let value = &uri[..end];
+                if !reserved && value.contains('/') {
+                    break;
+                }
+                if match_parts(rest, &uri[end..], variables) {
+                    variables.insert(name.clone(), value.to_string());
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_template() {
+        let template = UriTemplate::parse("file:///logs/{date}/{name}.log").unwrap();
+        let variables = template.matches("file:///logs/2025-01-01/app.server.log").unwrap();
+        assert_eq!(variables["date"], "2025-01-01");
+        assert_eq!(variables["name"], "app.server");
+        assert!(template.matches("file:///logs/2025-01-01/a/b.log").is_none());
+        assert!(template.matches("file:///logs/2025-01-01/.log").is_none());
+
+        let template = UriTemplate::parse("repo://{owner}/{+path}").unwrap();
+        let variables = template.matches("repo://open1s/src/server.rs").unwrap();
+        assert_eq!(variables["owner"], "open1s");
+        assert_eq!(variables["path"], "src/server.rs");
+
+        assert!(UriTemplate::parse("file:///{name").is_err());
+        assert!(UriTemplate::parse("search://{?q}").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rioc::{Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+
+use crate::support::definition::McpLayer;
+use crate::transport::stdstream::StdStreamTransport;
+use crate::MCPError;
+
+/// Grace period between SIGTERM and SIGKILL on shutdown.
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Command line of a stdio MCP server to launch.
+#[derive(Debug, Clone)]
+pub struct ChildProcessConfig {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub current_dir: Option<PathBuf>,
+}
+
+impl ChildProcessConfig {
+    pub fn new(program: &str) -> Self {
+        ChildProcessConfig {
+            program: program.to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: None,
+        }
+    }
+
+    pub fn with_arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn with_args<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(|a| a.as_ref().to_string()));
+        self
+    }
+
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+}
+
+/// Owns the child, terminating and reaping it when the last transport goes away.
+struct ChildGuard {
+    child: Mutex<Child>,
+    program: String,
+}
+
+impl ChildGuard {
+    fn terminate(&self) -> Result<ExitStatus, MCPError> {
+        let mut child = self.child.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock child process".to_string()))?;
+        let io_error = |e: std::io::Error| MCPError::Transport(format!("Failed to stop {}: {}", self.program, e));
+
+        if let Some(status) = child.try_wait().map_err(io_error)? {
+            return Ok(status);
+        }
+
+        //ask politely first, stdio servers usually exit on SIGTERM
+        let group = -(child.id() as libc::pid_t);
+        unsafe {
+            libc::kill(group, libc::SIGTERM);
+        }
+        let start = Instant::now();
+        while start.elapsed() < TERMINATE_TIMEOUT {
+            if let Some(status) = child.try_wait().map_err(io_error)? {
+                return Ok(status);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        warn!("{} did not exit after SIGTERM, killing it", self.program);
+        unsafe {
+            libc::kill(group, libc::SIGKILL);
+        }
+        child.wait().map_err(io_error)
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.terminate() {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Client transport that launches a stdio MCP server and speaks to it over
+/// the child's stdin and stdout. Anything the child writes to stderr is
+/// forwarded to the log.
+#[derive(Clone)]
+pub struct ChildProcessTransport {
+    io: StdStreamTransport,
+    child: Arc<ChildGuard>,
+}
+
+impl ChildProcessTransport {
+    pub fn spawn(config: ChildProcessConfig) -> Result<Self, MCPError> {
+        let mut command = Command::new(&config.program);
+        command.args(&config.args)
+            .envs(config.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            //own process group so wrappers like `sh -c` or `npx` go down with it
+            .process_group(0);
+        if let Some(dir) = &config.current_dir {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn()
+            .map_err(|e| MCPError::Transport(format!("Failed to spawn {}: {}", config.program, e)))?;
+        info!("Spawned {} with pid {}", config.program, child.id());
+
+        let missing = || MCPError::Transport("Child process pipes unavailable".to_string());
+        let stdin = child.stdin.take().ok_or_else(missing)?;
+        let stdout = child.stdout.take().ok_or_else(missing)?;
+        let stderr = child.stderr.take().ok_or_else(missing)?;
+
+        let program = config.program.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                match line {
+                    Ok(line) => info!("[{}] {}", program, line),
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let io = StdStreamTransport::with_streams(stdout, stdin)
+            .with_session_id(&format!("child-{}", child.id()));
+        Ok(ChildProcessTransport {
+            io,
+            child: Arc::new(ChildGuard {
+                child: Mutex::new(child),
+                program: config.program,
+            }),
+        })
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.child.lock().ok().map(|c| c.id())
+    }
+
+    /// Whether the child is still running.
+    pub fn is_running(&self) -> bool {
+        self.child.child.lock()
+            .map(|mut c| matches!(c.try_wait(), Ok(None)))
+            .unwrap_or(false)
+    }
+
+    /// Terminate the child and reap it, returning its exit status.
+    pub fn shutdown(&self) -> Result<ExitStatus, MCPError> {
+        let status = self.child.terminate()?;
+        info!("{} exited with {}", self.child.program, status);
+        Ok(status)
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        self.io.layer0_tx(data)
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        self.io.layer0_rx()
+    }
+}
+
+impl McpLayer for ChildProcessTransport {
+    fn create(&self) -> SharedLayer {
+        //the closures keep the child alive as long as the chain
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("child process transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_process_transport() {
+        let config = ChildProcessConfig::new("sh")
+            .with_args(["-c", "echo started >&2; cat"])
+            .with_env("MCPS_TEST", "1")
+            .with_current_dir(std::env::temp_dir());
+        let transport = ChildProcessTransport::spawn(config).unwrap();
+        assert!(transport.is_running());
+
+        //cat echoes every line back
+        let request = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}";
+        transport.layer0_tx(PayLoad {
+            data: Some(request.to_string()),
+            ctx: None,
+        }).unwrap();
+        let echoed = transport.layer0_rx().unwrap();
+        assert_eq!(echoed.data.unwrap(), request);
+
+        transport.shutdown().unwrap();
+        assert!(!transport.is_running());
+        assert!(transport.layer0_rx().is_err());
+    }
+
+    #[test]
+    fn test_child_process_missing_program() {
+        assert!(ChildProcessTransport::spawn(ChildProcessConfig::new("/nonexistent/mcp-server")).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+use serde_json::Value;
+
+use crate::config::transport_config::Framing;
+use crate::support::definition::McpLayer;
+use crate::MCPError;
+
+/// Upper bound for a single framed message.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Upper bound for the header block of a `Content-Length` frame.
+pub const MAX_HEADER_LEN: usize = 8 * 1024;
+
+const CONTENT_LENGTH: &str = "content-length";
+
+/// Frame one message for the wire.
+pub fn encode_frame(framing: Framing, data: &str) -> Vec<u8> {
+    match framing {
+        Framing::Newline => {
+            //messages must not contain embedded newlines
+            let mut line = if data.contains('\n') {
+                serde_json::from_str::<Value>(data)
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|_| data.replace('\n', " "))
+            } else {
+                data.to_string()
+            };
+            line.push('\n');
+            line.into_bytes()
+        }
+        Framing::Length => {
+            let mut frame = Vec::with_capacity(data.len() + 4);
+            frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            frame.extend_from_slice(data.as_bytes());
+            frame
+        }
+        Framing::ContentLength => {
+            let mut frame = format!("Content-Length: {}\r\n\r\n", data.len()).into_bytes();
+            frame.extend_from_slice(data.as_bytes());
+            frame
+        }
+    }
+}
+
+/// Body length announced by a `Content-Length` header block. Other headers,
+/// such as `Content-Type`, are ignored.
+pub fn parse_content_length(headers: &str) -> Result<usize, MCPError> {
+    let len = headers.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(CONTENT_LENGTH))
+        .ok_or_else(|| MCPError::Transport("Missing Content-Length header".to_string()))?
+        .1.trim().parse::<usize>()
+        .map_err(|e| MCPError::Transport(format!("Invalid Content-Length header: {}", e)))?;
+    if len > MAX_FRAME_LEN {
+        return Err(MCPError::Transport(format!("Frame of {} bytes too large", len)));
+    }
+    Ok(len)
+}
+
+/// Reassembles messages from bytes that arrive in arbitrary pieces.
+pub struct FrameDecoder {
+    framing: Framing,
+    buf: Vec<u8>,
+    /// Bytes of `buf` already searched for a newline.
+    scanned: usize,
+    /// Dropping the rest of an overlong line, up to its newline.
+    skip_line: bool,
+    /// Frame boundaries were lost, nothing after that can be decoded.
+    broken: bool,
+}
+
+impl FrameDecoder {
+    pub fn new(framing: Framing) -> Self {
+        FrameDecoder {
+            framing,
+            buf: Vec::new(),
+            scanned: 0,
+            skip_line: false,
+            broken: false,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        if !self.broken {
+            self.buf.extend_from_slice(bytes);
+        }
+    }
+
+    /// Bytes received but not yet returned as a message.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Next complete message, `None` until enough bytes have been pushed.
+    ///
+    /// A malformed frame is dropped and reported as `MCPError::Transport`,
+    /// the next call goes on with the bytes after it. `MCPError::Closed`
+    /// means the stream cannot be resynced, e.g. after a bogus length prefix.
+    pub fn next_message(&mut self) -> Result<Option<String>, MCPError> {
+        loop {
+            if self.broken {
+                return Err(MCPError::Closed("frame boundaries lost".to_string()));
+            }
+            let (start, end) = match self.framing {
+                Framing::Newline => match self.buf[self.scanned..].iter().position(|b| *b == b'\n') {
+                    Some(pos) if self.skip_line => {
+                        self.buf.drain(..=self.scanned + pos);
+                        self.scanned = 0;
+                        self.skip_line = false;
+                        continue;
+                    }
+                    Some(pos) => (0, self.scanned + pos + 1),
+                    None if self.skip_line => {
+                        self.buf.clear();
+                        self.scanned = 0;
+                        return Ok(None);
+                    }
+                    None if self.buf.len() > MAX_FRAME_LEN => {
+                        self.buf.clear();
+                        self.scanned = 0;
+                        self.skip_line = true;
+                        return Err(MCPError::Transport(format!("Line longer than {} bytes dropped", MAX_FRAME_LEN)));
+                    }
+                    None => {
+                        self.scanned = self.buf.len();
+                        return Ok(None);
+                    }
+                },
+                Framing::Length => {
+                    if self.buf.len() < 4 {
+                        return Ok(None);
+                    }
+                    let len = u32::from_be_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]]) as usize;
+                    if len > MAX_FRAME_LEN {
+                        //no way to tell where the next frame starts
+                        warn!("Frame of {} bytes too large, giving up on the stream", len);
+                        self.buf.clear();
+                        self.broken = true;
+                        continue;
+                    }
+                    (4, 4 + len)
+                }
+                Framing::ContentLength => {
+                    //tolerate stray blank lines between messages
+                    let blank = self.buf.iter().take_while(|b| matches!(b, b'\r' | b'\n')).count();
+                    self.buf.drain(..blank);
+                    let Some((header_len, separator)) = find_header_end(&self.buf) else {
+                        if self.buf.len() > MAX_HEADER_LEN {
+                            warn!("No end of headers in {} bytes, giving up on the stream", self.buf.len());
+                            self.buf.clear();
+                            self.broken = true;
+                            continue;
+                        }
+                        return Ok(None);
+                    };
+                    let start = header_len + separator;
+                    let headers = String::from_utf8_lossy(&self.buf[..header_len]).into_owned();
+                    match parse_content_length(&headers) {
+                        Ok(len) => (start, start + len),
+                        Err(e) => {
+                            //skip the header block, the next one starts a new message
+                            self.buf.drain(..start);
+                            return Err(e);
+                        }
+                    }
+                }
+            };
+            if self.buf.len() < end {
+                return Ok(None);
+            }
+
+            let frame: Vec<u8> = self.buf.drain(..end).skip(start).collect();
+            self.scanned = 0;
+            let message = String::from_utf8(frame)
+                .map_err(|e| MCPError::Transport(format!("Frame is not valid utf-8: {}", e)))?;
+            //blank lines between messages are noise, not messages
+            match self.framing {
+                Framing::Newline if message.trim().is_empty() => continue,
+                Framing::Newline => return Ok(Some(message.trim().to_string())),
+                _ => return Ok(Some(message)),
+            }
+        }
+    }
+}
+
+/// Length of the header block and of the blank line closing it.
+fn find_header_end(buf: &[u8]) -> Option<(usize, usize)> {
+    let crlf = buf.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| (pos, 4));
+    let lf = buf.windows(2).position(|w| w == b"\n\n").map(|pos| (pos, 2));
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) => Some(if crlf.0 < lf.0 { crlf } else { lf }),
+        (crlf, lf) => crlf.or(lf),
+    }
+}
+
+/// Frames messages on top of a byte stream transport. The transport below
+/// (e.g. `StdStreamTransport::raw`) hands up text as it arrives; this layer
+/// pulls from it until a whole message is buffered, so one read carrying
+/// several messages or a message spread over many reads both work.
+///
+/// ```ignore
+/// let stream = StdStreamTransport::new().raw();
+/// let framed = FramingLayer::new(&stream, Framing::ContentLength)?;
+/// server.add_transport_layer(framed.create());
+/// ```
+pub struct FramingLayer {
+    inner: SharedLayer,
+    framing: Framing,
+}
+
+impl FramingLayer {
+    /// Newline or `Content-Length` framing. Binary length prefixes cannot
+    /// travel as text, use `SocketTransport` with `Framing::Length` instead.
+    pub fn new(transport: &impl McpLayer, framing: Framing) -> Result<Self, MCPError> {
+        if framing == Framing::Length {
+            return Err(MCPError::Transport("Length framing is not supported on text streams".to_string()));
+        }
+        Ok(FramingLayer {
+            inner: transport.create(),
+            framing,
+        })
+    }
+}
+
+impl McpLayer for FramingLayer {
+    fn create(&self) -> SharedLayer {
+        let rx_inner = self.inner.clone();
+        let tx_inner = self.inner.clone();
+        let framing = self.framing;
+        //a byte stream carries a single session, keep the context it reported
+        let state: Arc<Mutex<(FrameDecoder, Option<ChainContext>)>> =
+            Arc::new(Mutex::new((FrameDecoder::new(framing), None)));
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let mut state = state.lock().map_err(|_| "Failed to lock frame decoder".to_string())?;
+                let (decoder, ctx) = &mut *state;
+                loop {
+                    if let Some(message) = decoder.next_message().map_err(super::inbound_error)? {
+                        return Ok(LayerResult {
+                            direction: Direction::Inbound,
+                            data: Some(PayLoad {
+                                data: Some(message),
+                                ctx: ctx.clone(),
+                            }),
+                        });
+                    }
+                    let chunk = rx_inner.borrow().handle_inbound(None)?
+                        .data.ok_or_else(|| "no data received".to_string())?;
+                    if let Some(data) = chunk.data {
+                        decoder.push(data.as_bytes());
+                    }
+                    if chunk.ctx.is_some() {
+                        *ctx = chunk.ctx;
+                    }
+                }
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                let data = req.data.ok_or_else(|| "no data to send".to_string())?;
+                let frame = String::from_utf8(encode_frame(framing, &data)).map_err(|e| e.to_string())?;
+                if let Err(e) = tx_inner.borrow().handle_outbound(Some(PayLoad {
+                    data: Some(frame),
+                    ctx: req.ctx,
+                })) {
+                    warn!("framing layer failed to send: {}", e);
+                    return Err(e);
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use crate::schema::schema::SESSION_ID_KEY;
+    use crate::transport::stdstream::StdStreamTransport;
+
+    use super::*;
+
+    #[test]
+    fn test_frame_decoder() {
+        let mut decoder = FrameDecoder::new(Framing::ContentLength);
+        let mut stream = encode_frame(Framing::ContentLength, "{\"id\":1}");
+        stream.extend_from_slice(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length: 8\r\n\r\n{\"id\":2}");
+        stream.extend_from_slice(b"Content-Length: 8\n\n{\"id\":3}");
+
+        //fed a byte at a time, messages only show up once complete
+        let mut messages = Vec::new();
+        for byte in &stream {
+            decoder.push(&[*byte]);
+            while let Some(message) = decoder.next_message().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages, vec!["{\"id\":1}", "{\"id\":2}", "{\"id\":3}"]);
+        assert_eq!(decoder.buffered(), 0);
+
+        let mut decoder = FrameDecoder::new(Framing::Newline);
+        decoder.push(b"{\"id\":1}\n\n{\"id\":2}\r\n{\"id\"");
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":2}");
+        assert_eq!(decoder.next_message().unwrap(), None);
+
+        let mut decoder = FrameDecoder::new(Framing::ContentLength);
+        decoder.push(b"Content-Type: text/plain\r\n\r\n");
+        assert!(decoder.next_message().is_err());
+    }
+
+    #[test]
+    fn test_frame_decoder_resync() {
+        //a bad frame is dropped, the valid one behind it still comes through
+        let mut decoder = FrameDecoder::new(Framing::ContentLength);
+        decoder.push(b"Content-Length: many\r\n\r\n\r\n");
+        decoder.push(&encode_frame(Framing::ContentLength, "{\"id\":1}"));
+        assert!(matches!(decoder.next_message(), Err(MCPError::Transport(_))));
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(decoder.buffered(), 0);
+
+        let mut decoder = FrameDecoder::new(Framing::ContentLength);
+        decoder.push(b"Content-Length: 2\r\n\r\n\xff\xfe");
+        decoder.push(&encode_frame(Framing::ContentLength, "{\"id\":2}"));
+        assert!(decoder.next_message().is_err());
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":2}");
+
+        let mut decoder = FrameDecoder::new(Framing::Newline);
+        decoder.push(b"\xff{\"id\":3}\n{\"id\":4}\n");
+        assert!(decoder.next_message().is_err());
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":4}");
+
+        //a length prefix cannot be resynced, the stream is done
+        let mut decoder = FrameDecoder::new(Framing::Length);
+        decoder.push(&u32::MAX.to_be_bytes());
+        decoder.push(&encode_frame(Framing::Length, "{\"id\":5}"));
+        assert!(matches!(decoder.next_message(), Err(MCPError::Closed(_))));
+        assert!(matches!(decoder.next_message(), Err(MCPError::Closed(_))));
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn test_frame_decoder_limits() {
+        //an endless line is dropped as it grows, the line after it survives
+        let mut decoder = FrameDecoder::new(Framing::Newline);
+        let chunk = vec![b'x'; 1024 * 1024];
+        let mut dropped = 0;
+        for _ in 0..(MAX_FRAME_LEN / chunk.len() + 8) {
+            decoder.push(&chunk);
+            if decoder.next_message().is_err() {
+                dropped += 1;
+            }
+            assert!(decoder.buffered() <= MAX_FRAME_LEN + chunk.len());
+        }
+        assert_eq!(dropped, 1);
+        decoder.push(b"xx\n{\"id\":1}\n");
+        assert_eq!(decoder.next_message().unwrap().unwrap(), "{\"id\":1}");
+
+        //headers that never end are not buffered without bound
+        let mut decoder = FrameDecoder::new(Framing::ContentLength);
+        decoder.push(b"Content-Length: 8\r\n");
+        decoder.push(&vec![b'x'; MAX_HEADER_LEN]);
+        assert!(matches!(decoder.next_message(), Err(MCPError::Closed(_))));
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    /// Hands out one byte per read, like a very slow pipe.
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_framing_layer() {
+        let ping = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\",\"params\":{\"name\":\"héllo\"}}";
+        let notification = "{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}";
+        let mut input = encode_frame(Framing::ContentLength, ping);
+        input.extend(encode_frame(Framing::ContentLength, notification));
+
+        //split reads, including one inside the multi-byte character
+        let output = SharedBuf::default();
+        let stream = StdStreamTransport::with_streams(Trickle(Cursor::new(input.clone())), output.clone()).raw();
+        let layer = FramingLayer::new(&stream, Framing::ContentLength).unwrap().create();
+        let payload = layer.borrow().handle_inbound(None).unwrap().data.unwrap();
+        assert_eq!(payload.data.unwrap(), ping);
+        assert_eq!(payload.ctx.unwrap().data.get(SESSION_ID_KEY).unwrap(), "local");
+        let payload = layer.borrow().handle_inbound(None).unwrap().data.unwrap();
+        assert_eq!(payload.data.unwrap(), notification);
+        assert!(layer.borrow().handle_inbound(None).is_err());
+
+        //both messages in a single read
+        let stream = StdStreamTransport::with_streams(Cursor::new(input), output.clone()).raw();
+        let layer = FramingLayer::new(&stream, Framing::ContentLength).unwrap().create();
+        assert_eq!(layer.borrow().handle_inbound(None).unwrap().data.unwrap().data.unwrap(), ping);
+        assert_eq!(layer.borrow().handle_inbound(None).unwrap().data.unwrap().data.unwrap(), notification);
+
+        let response = "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}";
+        layer.borrow().handle_outbound(Some(PayLoad {
+            data: Some(response.to_string()),
+            ctx: None,
+        })).unwrap();
+        assert_eq!(output.0.lock().unwrap().as_slice(), encode_frame(Framing::ContentLength, response).as_slice());
+
+        assert!(FramingLayer::new(&stream, Framing::Length).is_err());
+    }
+}
@@ -15,35 +15,41 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-#![allow(unused)]
-
-
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
-use std::io::Read;
-use std::net::TcpListener;
-use std::path::Path;
+use std::io::{BufReader, Read};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use crate::config::transport_config::HttpTransportConfig;
-use crate::schema::schema::SESSION_ID_KEY;
+use crate::schema::schema::{error_codes, RequestId, SESSION_ID_KEY};
 use crate::support::definition::McpLayer;
-use crate::support::shared_memory::{MemoryDuplex, SharedMemory};
+use crate::support::sessons::SESSION_STORE;
+use crate::transport::sse::{SseEvent, SseReader, SseWriter};
 use crate::MCPError;
-use bytes::BufMut;
-use disruptor::{Producer, Sequence};
-use rand::rand_core::le;
-use rioc::{ChainContext, Direction, Layer, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use dashmap::DashMap;
+use disruptor::Producer;
+use log::{info, warn};
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
 use crate::support::ControlBus;
-use crate::support::disruptor::{DisruptorProcessorCallback, DisruptorWriter};
-use crate::support::disruptor::DisruptorFactory;
-use ibuf::{MBuf, MPool};
 
-use tiny_http::{Request, Response, SslConfig};
+use tiny_http::{Header, Method, Request, Response, SslConfig};
 use tiny_http::{Server};
 
+/// Header carrying the session issued on initialize.
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_DELAY: Duration = Duration::from_millis(200);
 
 pub struct ServerBuilder {
     config: HttpTransportConfig,
@@ -93,7 +99,7 @@ impl ServerBuilder {
         let port = self.config.port;
         let bind_addr = format!("{}:{}", ip, port);
 
-        println!("Starting HTTP server on {}", bind_addr);
+        info!("Starting HTTP server on {}", bind_addr);
 
         match ssl_config {
             Some(config) => {
@@ -110,147 +116,943 @@ impl ServerBuilder {
     }
 }
 
+/// Event routed to an SSE stream, flagged when it answers a client request.
+type StreamItem = (bool, SseEvent);
+
+/// Stream id paired with the channel feeding its connection.
+type StreamTarget = (u64, Sender<StreamItem>);
+
+struct HttpSession {
+    /// Standalone stream opened by GET, for server initiated messages.
+    stream: Option<StreamTarget>,
+    /// Id of the latest standalone stream, still resumable once it dropped.
+    standalone: Option<u64>,
+    /// Opened through the 2024-11-05 HTTP+SSE handshake, every message
+    /// including responses goes over `stream`.
+    legacy: bool,
+    /// Recently sent events as (stream, seq, event), replayed on resume.
+    replay: VecDeque<(u64, u64, SseEvent)>,
+    next_seq: u64,
+    /// Last time a request of the session arrived or finished.
+    last_active: Instant,
+}
+
+impl Default for HttpSession {
+    fn default() -> Self {
+        HttpSession {
+            stream: None,
+            standalone: None,
+            legacy: false,
+            replay: VecDeque::new(),
+            next_seq: 0,
+            last_active: Instant::now(),
+        }
+    }
+}
+
+impl HttpSession {
+    /// Assign the next event id on `stream_id` and keep the event for replay.
+    fn record(&mut self, stream_id: u64, data: &str, capacity: usize) -> SseEvent {
+        self.next_seq += 1;
+        let mut event = SseEvent::message(data);
+        event.id = Some(format!("{}-{}", stream_id, self.next_seq));
+
+        self.replay.push_back((stream_id, self.next_seq, event.clone()));
+        while self.replay.len() > capacity {
+            self.replay.pop_front();
+        }
+        event
+    }
+
+    fn replay_after(&self, stream_id: u64, seq: u64) -> Vec<SseEvent> {
+        self.replay.iter()
+            .filter(|(stream, s, _)| *stream == stream_id && *s > seq)
+            .map(|(_, _, event)| event.clone())
+            .collect()
+    }
+}
+
+/// State shared between the accept loop, request threads and the layer.
+struct HttpShared {
+    config: HttpTransportConfig,
+    sessions: DashMap<String, HttpSession>,
+    pending: DashMap<(String, RequestId), StreamTarget>,
+    inbound: Sender<PayLoad>,
+    next_stream: AtomicU64,
+    /// Requests currently served, bounded by `max_concurrent_requests`.
+    active_requests: AtomicUsize,
+    closed: AtomicBool,
+}
+
+/// Frees a request's slot in `active_requests` once it is served.
+struct RequestSlot(Arc<HttpShared>);
+
+impl Drop for RequestSlot {
+    fn drop(&mut self) {
+        self.0.active_requests.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Stops the accept loop once the last transport clone goes away.
+struct StopGuard {
+    control_bus: Arc<ControlBus>,
+    shared: Arc<HttpShared>,
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Ok(mut tx) = self.control_bus.clone_tx() {
+            tx.publish(|e| {
+                *e = 1;
+            });
+        }
+    }
+}
+
+/// Client side of the transport, talking to a remote MCP endpoint.
+struct HttpClient {
+    url: String,
+    http: reqwest::blocking::Client,
+    session_id: Mutex<Option<String>>,
+}
 
-/// Standard IO transport
+/// Streamable HTTP transport (MCP 2025-03-26)
 #[derive(Clone)]
 pub struct HttpStreamTransport{
     control_bus: Arc<ControlBus>,
     is_server: bool,
     server: Option<Arc<Server>>,
+    client: Option<Arc<HttpClient>>,
+    shared: Arc<HttpShared>,
+    inbound_rx: Receiver<PayLoad>,
+    _guard: Arc<StopGuard>,
 }
 
 impl HttpStreamTransport {
     pub fn new(config: HttpTransportConfig, is_server: bool) -> Self {
-        let server = ServerBuilder::new(config).build();
+        let (server, client) = if is_server {
+            let server = ServerBuilder::new(config.clone()).build()
+                .expect("Failed to start http server");
+            (Some(Arc::new(server)), None)
+        } else {
+            let scheme = if config.enable_tls { "https" } else { "http" };
+            //SSE responses may stay open for as long as a request runs
+            let http = reqwest::blocking::Client::builder()
+                .timeout(None)
+                .build()
+                .expect("Failed to create http client");
+            let client = HttpClient {
+                url: format!("{}://{}:{}{}", scheme, config.ip_address, config.port, config.endpoint),
+                http,
+                session_id: Mutex::new(None),
+            };
+            (None, Some(Arc::new(client)))
+        };
 
+        let (inbound, inbound_rx) = unbounded();
+        let shared = Arc::new(HttpShared {
+            config,
+            sessions: DashMap::new(),
+            pending: DashMap::new(),
+            inbound,
+            next_stream: AtomicU64::new(0),
+            active_requests: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        });
+
+        let control_bus = Arc::new(ControlBus::new());
         HttpStreamTransport {
-            control_bus: Arc::new(ControlBus::new()),
-            server: Some(Arc::new(server.unwrap())),
+            control_bus: control_bus.clone(),
+            server,
+            client,
             is_server,
+            shared: shared.clone(),
+            inbound_rx,
+            _guard: Arc::new(StopGuard { control_bus, shared }),
         }
     }
 
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// Session issued by the remote server on initialize.
+    pub fn session_id(&self) -> Option<String> {
+        self.client.as_ref().and_then(|c| c.session_id.lock().unwrap().clone())
+    }
+
+    /// Address the server is listening on, useful when binding port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.as_ref().and_then(|s| s.server_addr().to_ip())
+    }
+
     pub fn start(&self) -> Result<JoinHandle<()>, MCPError> {
-        let server = self.server.as_ref().unwrap();
-        let mut rx = self.control_bus.clone_rx().unwrap();
-        let server = server.clone();
+        let server = self.server.clone()
+            .ok_or_else(|| MCPError::Transport("Not a server transport".to_string()))?;
+        let mut rx = self.control_bus.clone_rx()?;
+        let shared = self.shared.clone();
 
         let handle = std::thread::spawn(move||{
+            let mut last_expiry = Instant::now();
             loop {
-                match rx.try_recv() {
-                    Ok(_) => {
-                        break;
-                    }
-                    Err(_) => {
-                    }
+                if rx.try_recv().is_ok() {
+                    break;
                 }
-                let req =  server.recv_timeout(Duration::from_millis(10));
-                if let Err(_) = req {
-                    continue;
+                if last_expiry.elapsed() >= EXPIRY_INTERVAL {
+                    shared.expire_sessions();
+                    last_expiry = Instant::now();
                 }
-                let req = req.unwrap();
-                if  let None = req {
+                let req = match server.recv_timeout(Duration::from_millis(10)) {
+                    Ok(Some(req)) => req,
+                    _ => continue,
+                };
+
+                //SSE responses hold the connection open, serve each on its own
+                //thread, turning requests away once too many are in flight
+                if shared.active_requests.fetch_add(1, Ordering::AcqRel) >= shared.config.max_concurrent_requests {
+                    shared.active_requests.fetch_sub(1, Ordering::AcqRel);
+                    respond_status(req, 503, "Too many concurrent requests");
                     continue;
                 }
-                let req = req.unwrap();
-
-                let response = Response::from_string("Hello World!");
-                req.respond(response);
+                let slot = RequestSlot(shared.clone());
+                std::thread::spawn(move || {
+                    slot.0.handle_request(req);
+                    drop(slot);
+                });
             }
         });
-        
+
         Ok(handle)
     }
 
     pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
-        let mut data = data.data.clone().ok_or_else(|| 
+        let session_id = data.ctx.as_ref()
+            .and_then(|ctx| ctx.data.get(SESSION_ID_KEY).cloned())
+            .unwrap_or_default();
+        let data = data.data.ok_or_else(||
             MCPError::Transport("Payload data is None".to_string()))?;
+        match &self.client {
+            Some(client) => client.post(&self.shared, data),
+            None => self.shared.route(&session_id, data),
+        }
+    }
 
-        // let mut buf = MBuf::with_capacity(1024);
-        // let data = data.as_bytes();
-        // let len = data.len() as u32;
-        // buf.append(&len.to_be_bytes());
-        // buf.append(data);
-
-        // self.pipe.write(&buf)
-        //     .map_err(|e| MCPError::Transport(format!("Failed to write to shared memory: {}", e)))?;    
+    /// Explicitly end the session on the remote server.
+    pub fn terminate_session(&self) -> Result<(), MCPError> {
+        let client = self.client.as_ref()
+            .ok_or_else(|| MCPError::Transport("Not a client transport".to_string()))?;
+        let session_id = client.session_id.lock().unwrap().take();
+        if let Some(session_id) = session_id {
+            client.http.delete(&client.url)
+                .header(SESSION_HEADER, session_id)
+                .send()
+                .map_err(|e| MCPError::Transport(format!("Failed to terminate session: {}", e)))?;
+        }
         Ok(())
     }
 
     pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
-        // let mut len_data = [0; 4];
+        loop {
+            match self.inbound_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(payload) => return Ok(payload),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shared.closed.load(Ordering::Acquire) {
+                        return Err(MCPError::Closed("http".to_string()));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(MCPError::Closed("http".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl HttpShared {
+    fn handle_request(&self, request: Request) {
+        let path = request.url().split('?').next().unwrap_or_default().to_string();
+        let legacy = self.config.legacy_sse
+            && (path == self.config.sse_endpoint || path == self.config.messages_endpoint);
+        if path != self.config.endpoint && !legacy {
+            respond_status(request, 404, "Not Found");
+            return;
+        }
+
+        if !self.origin_allowed(&request) {
+            respond_status(request, 403, "Origin not allowed");
+            return;
+        }
+
+        if legacy {
+            match request.method() {
+                Method::Get if path == self.config.sse_endpoint => self.handle_legacy_stream(request),
+                Method::Post if path == self.config.messages_endpoint => self.handle_legacy_post(request),
+                _ => respond_status(request, 405, "Method Not Allowed"),
+            }
+            return;
+        }
+
+        match request.method() {
+            Method::Post => self.handle_post(request),
+            Method::Get => self.handle_get(request),
+            Method::Delete => self.handle_delete(request),
+            _ => respond_status(request, 405, "Method Not Allowed"),
+        }
+    }
+
+    fn origin_allowed(&self, request: &Request) -> bool {
+        match (&self.config.allowed_origins, header_value(request, "Origin")) {
+            (Some(allowed), Some(origin)) => allowed.contains(&origin),
+            _ => true,
+        }
+    }
+
+    fn handle_post(&self, mut request: Request) {
+        let mut body = String::new();
+        if let Err(e) = request.as_reader().read_to_string(&mut body) {
+            warn!("Failed to read request body: {}", e);
+            respond_status(request, 400, "Failed to read request body");
+            return;
+        }
+
+        let (messages, batch) = match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Array(items)) => (items, true),
+            Ok(value) => (vec![value], false),
+            Err(e) => {
+                respond_error(request, 400, error_codes::PARSE_ERROR, &format!("Parse error: {}", e));
+                return;
+            }
+        };
+        if messages.is_empty() {
+            respond_error(request, 400, error_codes::INVALID_REQUEST, "Empty batch");
+            return;
+        }
+
+        let is_initialize = messages.iter()
+            .any(|m| m.get("method").and_then(Value::as_str) == Some("initialize"));
+        let session_id = if is_initialize {
+            let session_id = new_session_id();
+            self.sessions.insert(session_id.clone(), HttpSession::default());
+            info!("Created http session {}", session_id);
+            session_id
+        } else {
+            match self.validate_session(&request) {
+                Ok(session_id) => session_id,
+                Err((code, message)) => {
+                    respond_status(request, code, message);
+                    return;
+                }
+            }
+        };
+
+        //requests expect an answer on this POST, everything else is just accepted
+        let request_ids: Vec<RequestId> = messages.iter()
+            .filter(|m| m.get("method").is_some())
+            .filter_map(|m| m.get("id"))
+            .filter_map(|id| serde_json::from_value(id.clone()).ok())
+            .collect();
+
+        let (tx, rx) = unbounded();
+        let stream_id = self.next_stream.fetch_add(1, Ordering::Relaxed);
+        for id in &request_ids {
+            self.pending.insert((session_id.clone(), id.clone()), (stream_id, tx.clone()));
+        }
+        drop(tx);
+
+        for message in messages {
+            self.push_inbound(&session_id, message.to_string());
+        }
+
+        if request_ids.is_empty() {
+            let response = Response::empty(202).with_header(session_header(&session_id));
+            let _ = request.respond(response);
+            return;
+        }
+
+        if self.config.json_response || !accepts(&request, "text/event-stream") {
+            self.respond_json(request, &session_id, rx, request_ids.len(), batch);
+
+            //drop whatever was not answered, the client is gone
+            for id in request_ids {
+                self.pending.remove(&(session_id.clone(), id));
+            }
+        } else {
+            //unanswered requests stay pending so a resumed stream picks them up
+            self.respond_sse(request, &session_id, rx, request_ids.len());
+        }
+        self.touch(&session_id);
+    }
+
+    fn respond_json(&self, request: Request, session_id: &str, rx: Receiver<StreamItem>, expected: usize, batch: bool) {
+        let mut responses = Vec::new();
+        while responses.len() < expected {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok((true, event)) => {
+                    responses.push(serde_json::from_str::<Value>(&event.data).unwrap_or(Value::Null));
+                }
+                //no stream to carry notifications in json mode
+                Ok((false, _)) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.closed.load(Ordering::Acquire) {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if responses.len() < expected {
+            respond_status(request, 503, "Server closed before responding");
+            return;
+        }
+
+        let body = if batch {
+            Value::Array(responses)
+        } else {
+            responses.remove(0)
+        };
+        let response = Response::from_string(body.to_string())
+            .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+            .with_header(session_header(session_id));
+        let _ = request.respond(response);
+    }
+
+    fn respond_sse(&self, request: Request, session_id: &str, rx: Receiver<StreamItem>, expected: usize) {
+        let headers = [(SESSION_HEADER, session_id.to_string())];
+        let mut writer = match SseWriter::start(request.into_writer(), &headers) {
+            Ok(writer) => writer,
+            Err(e) => {
+                warn!("Failed to open SSE stream: {}", e);
+                return;
+            }
+        };
+
+        let mut answered = 0;
+        while answered < expected {
+            match rx.recv_timeout(KEEPALIVE_INTERVAL) {
+                Ok((is_response, event)) => {
+                    if writer.send(&event).is_err() {
+                        return;
+                    }
+                    if is_response {
+                        answered += 1;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.closed.load(Ordering::Acquire) || writer.comment("keepalive").is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        let _ = writer.finish();
+    }
+
+    fn handle_get(&self, request: Request) {
+        if !accepts(&request, "text/event-stream") {
+            respond_status(request, 405, "Method Not Allowed");
+            return;
+        }
+        let session_id = match self.validate_session(&request) {
+            Ok(session_id) => session_id,
+            Err((code, message)) => {
+                respond_status(request, code, message);
+                return;
+            }
+        };
+
+        let (tx, rx) = unbounded();
+        let resume = header_value(&request, "Last-Event-ID").and_then(|id| parse_event_id(&id));
+        let (stream_id, missed) = match self.sessions.get_mut(&session_id) {
+            Some(mut session) => match resume {
+                Some((stream_id, seq)) if session.standalone != Some(stream_id) => {
+                    //requests still running on a broken POST stream continue on this one,
+                    //which closes once the last of them is answered
+                    for mut entry in self.pending.iter_mut() {
+                        if entry.key().0 == session_id && entry.value().0 == stream_id {
+                            entry.value_mut().1 = tx.clone();
+                        }
+                    }
+                    drop(tx);
+                    (stream_id, session.replay_after(stream_id, seq))
+                }
+                resume => {
+                    let (stream_id, missed) = match resume {
+                        Some((stream_id, seq)) => (stream_id, session.replay_after(stream_id, seq)),
+                        None => (self.next_stream.fetch_add(1, Ordering::Relaxed), Vec::new()),
+                    };
+                    session.standalone = Some(stream_id);
+                    session.stream = Some((stream_id, tx));
+                    (stream_id, missed)
+                }
+            },
+            None => {
+                respond_status(request, 404, "Session not found");
+                return;
+            }
+        };
+        if !missed.is_empty() {
+            info!("Replaying {} events on stream {} of session {}", missed.len(), stream_id, session_id);
+        }
+
+        let headers = [(SESSION_HEADER, session_id.clone())];
+        if let Ok(mut writer) = SseWriter::start(request.into_writer(), &headers) {
+            if missed.iter().try_for_each(|event| writer.send(event)).is_ok() {
+                self.forward_stream(writer, rx);
+            }
+        }
+
+        if let Some(mut session) = self.sessions.get_mut(&session_id) {
+            if matches!(session.stream, Some((id, _)) if id == stream_id) {
+                session.stream = None;
+            }
+            session.last_active = Instant::now();
+        }
+    }
+
+    /// Forward routed events to a standalone stream until the peer goes away.
+    fn forward_stream(&self, mut writer: SseWriter, rx: Receiver<StreamItem>) {
+        loop {
+            match rx.recv_timeout(KEEPALIVE_INTERVAL) {
+                Ok((_, event)) => {
+                    if writer.send(&event).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.closed.load(Ordering::Acquire) || writer.comment("keepalive").is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = writer.finish();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Legacy `GET /sse`: open a session whose stream carries every message,
+    /// announcing where the client should POST with an `endpoint` event.
+    fn handle_legacy_stream(&self, request: Request) {
+        let session_id = new_session_id();
+        let stream_id = self.next_stream.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = unbounded();
+        self.sessions.insert(session_id.clone(), HttpSession {
+            stream: Some((stream_id, tx)),
+            legacy: true,
+            ..Default::default()
+        });
+        info!("Created legacy sse session {}", session_id);
+
+        let endpoint = SseEvent {
+            id: None,
+            event: Some("endpoint".to_string()),
+            data: format!("{}?sessionId={}", self.config.messages_endpoint, session_id),
+        };
+        if let Ok(mut writer) = SseWriter::start(request.into_writer(), &[]) {
+            if writer.send(&endpoint).is_ok() {
+                self.forward_stream(writer, rx);
+            }
+        }
+
+        //the session lives as long as its stream
+        self.sessions.remove(&session_id);
+        self.pending.retain(|(sid, _), _| *sid != session_id);
+        SESSION_STORE.invalidate_session(&session_id);
+        info!("Closed legacy sse session {}", session_id);
+    }
+
+    /// Legacy `POST /messages?sessionId=...`, answers arrive on the session stream.
+    fn handle_legacy_post(&self, mut request: Request) {
+        let session_id = request.url().split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|p| p.strip_prefix("sessionId=")))
+            .map(|sid| sid.to_string());
+        let session_id = match session_id {
+            Some(sid) if self.sessions.contains_key(&sid) => sid,
+            Some(_) => {
+                respond_status(request, 404, "Session not found");
+                return;
+            }
+            None => {
+                respond_status(request, 400, "Missing sessionId");
+                return;
+            }
+        };
+
+        let mut body = String::new();
+        if request.as_reader().read_to_string(&mut body).is_err() {
+            respond_status(request, 400, "Failed to read request body");
+            return;
+        }
+        let messages = match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Array(items)) => items,
+            Ok(value) => vec![value],
+            Err(e) => {
+                respond_error(request, 400, error_codes::PARSE_ERROR, &format!("Parse error: {}", e));
+                return;
+            }
+        };
+
+        for message in messages {
+            self.push_inbound(&session_id, message.to_string());
+        }
+        respond_status(request, 202, "Accepted");
+    }
 
-        // //read len first
-        // let bytes_read = self.pipe.read(&mut len_data)
-        //     .map_err(|e| MCPError::Transport(format!("Failed to read from shared memory: {}", e)))?;
+    fn handle_delete(&self, request: Request) {
+        let session_id = match self.validate_session(&request) {
+            Ok(session_id) => session_id,
+            Err((code, message)) => {
+                respond_status(request, code, message);
+                return;
+            }
+        };
+
+        self.sessions.remove(&session_id);
+        self.pending.retain(|(sid, _), _| *sid != session_id);
+        SESSION_STORE.invalidate_session(&session_id);
+        info!("Terminated http session {}", session_id);
+        respond_status(request, 200, "");
+    }
+
+    fn validate_session(&self, request: &Request) -> Result<String, (u16, &'static str)> {
+        match header_value(request, SESSION_HEADER) {
+            None => Err((400, "Missing Mcp-Session-Id header")),
+            Some(session_id) if !self.touch(&session_id) => Err((404, "Session not found")),
+            Some(session_id) => Ok(session_id),
+        }
+    }
+
+    /// Mark the session as in use, false when there is no such session.
+    fn touch(&self, session_id: &str) -> bool {
+        match self.sessions.get_mut(session_id) {
+            Some(mut session) => {
+                session.last_active = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop sessions that saw no request for `session_idle_timeout`, together
+    /// with their replay buffers. A session with an open stream or a request
+    /// still waiting for its answer is in use.
+    fn expire_sessions(&self) {
+        if self.config.session_idle_timeout == 0 {
+            return;
+        }
+        let timeout = Duration::from_secs(self.config.session_idle_timeout);
+        let idle = |session: &HttpSession| session.stream.is_none() && session.last_active.elapsed() >= timeout;
+
+        let candidates: Vec<String> = self.sessions.iter()
+            .filter(|session| idle(session.value()))
+            .map(|session| session.key().clone())
+            .collect();
+        for session_id in candidates {
+            if self.pending.iter().any(|entry| entry.key().0 == session_id) {
+                continue;
+            }
+            //it may have been used since we looked
+            if self.sessions.remove_if(&session_id, |_, session| idle(session)).is_some() {
+                SESSION_STORE.invalidate_session(&session_id);
+                info!("Expired idle http session {}", session_id);
+            }
+        }
+    }
 
-        // let len = u32::from_be_bytes(len_data);
-        // let mut data = vec![0; len as usize];
+    /// Deliver an outbound message: responses go back on the POST that carried
+    /// the request, server initiated messages prefer the session's GET stream.
+    /// The session stays locked while delivering so a concurrent resume never
+    /// misses an event.
+    fn route(&self, session_id: &str, data: String) -> Result<(), MCPError> {
+        let message: Value = serde_json::from_str(&data)?;
+        let is_response = message.get("method").is_none();
 
-        
-        // let bytes_read = self.pipe.read(&mut data)
-        //     .map_err(|e| MCPError::Transport(format!("Failed to read from shared memory: {}", e)))?;
+        let mut session = match self.sessions.get_mut(session_id) {
+            Some(session) => session,
+            None => {
+                warn!("Unknown http session {}, message dropped", session_id);
+                return Ok(());
+            }
+        };
 
-        // let mut ctx = ChainContext{
-        //     data: HashMap::new(),
-        // };
+        let target = if is_response {
+            let id = message.get("id")
+                .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok())
+                .ok_or_else(|| MCPError::Transport("Response without id".to_string()))?;
+            let target = self.pending.remove(&(session_id.to_string(), id.clone()))
+                .map(|(_, target)| target)
+                .or_else(|| session.stream.clone().filter(|_| session.legacy));
+            if target.is_none() {
+                warn!("No pending http request {:?} in session {}", id, session_id);
+            }
+            target
+        } else {
+            let target = session.stream.clone().or_else(|| {
+                self.pending.iter()
+                    .find(|entry| entry.key().0 == session_id)
+                    .map(|entry| entry.value().clone())
+            });
+            if target.is_none() {
+                warn!("No open stream for session {}, message dropped", session_id);
+            }
+            target
+        };
 
-        // //we need a way 
-        // ctx.data.insert(SESSION_ID_KEY.to_owned(), "local".to_string());
-        // let payload = String::from_utf8(data).map_err(|e| MCPError::Transport(format!("Failed to convert to string: {}", e)))?;
-        // Ok(PayLoad{
-        //     data: Some(payload),
-        //     ctx: Some(ctx),
-        // })
-        todo!();
+        if let Some((stream_id, tx)) = target {
+            let event = session.record(stream_id, &data, self.config.replay_buffer);
+            //a send error only means the peer is gone, the event stays replayable
+            let _ = tx.send((is_response, event));
+        }
+        Ok(())
     }
 }
 
+impl HttpClient {
+    fn post(self: &Arc<Self>, shared: &Arc<HttpShared>, data: String) -> Result<(), MCPError> {
+        let session_id = self.session_id.lock().unwrap().clone();
+        let mut request = self.http.post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .body(data);
+        if let Some(session_id) = &session_id {
+            request = request.header(SESSION_HEADER, session_id);
+        }
 
-impl Drop for HttpStreamTransport {
-    fn drop(&mut self) {
-       let tx = self.control_bus.clone_tx();
-       tx.unwrap().publish(|e|{
-          *e = 1;
-       });
+        let response = request.send()
+            .map_err(|e| MCPError::Transport(format!("Failed to send http request: {}", e)))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && session_id.is_some() {
+            *self.session_id.lock().unwrap() = None;
+            return Err(MCPError::Transport("Http session expired".to_string()));
+        }
+        if !status.is_success() {
+            return Err(MCPError::Transport(format!("Http request failed with status {}", status)));
+        }
+
+        let issued = response.headers().get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let session_id = match (session_id, issued) {
+            (None, Some(issued)) => {
+                info!("Joined http session {}", issued);
+                *self.session_id.lock().unwrap() = Some(issued.clone());
+                self.open_stream(shared.clone(), issued.clone());
+                issued
+            }
+            (current, _) => current.unwrap_or_default(),
+        };
+
+        if status == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let is_sse = response.headers().get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.starts_with("text/event-stream"))
+            .unwrap_or(false);
+        if is_sse {
+            //read the stream in the background so the caller can keep sending
+            let client = self.clone();
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                client.consume_stream(&shared, SseReader::new(BufReader::new(response)), &session_id);
+            });
+            return Ok(());
+        }
+
+        let body = response.text()
+            .map_err(|e| MCPError::Transport(format!("Failed to read http response: {}", e)))?;
+        if body.trim().is_empty() {
+            return Ok(());
+        }
+        let messages = match serde_json::from_str::<Value>(&body)? {
+            Value::Array(items) => items,
+            value => vec![value],
+        };
+        for message in messages {
+            shared.push_inbound(&session_id, message.to_string());
+        }
+        Ok(())
+    }
+
+    /// Open the optional GET stream carrying server initiated messages.
+    fn open_stream(self: &Arc<Self>, shared: Arc<HttpShared>, session_id: String) {
+        let client = self.clone();
+        std::thread::spawn(move || {
+            let response = match client.get_stream(&session_id, None) {
+                Ok(response) if response.status().is_success() => response,
+                Ok(response) => {
+                    info!("Server offers no standalone stream: {}", response.status());
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to open http stream: {}", e);
+                    return;
+                }
+            };
+            client.consume_stream(&shared, SseReader::new(BufReader::new(response)), &session_id);
+        });
+    }
+
+    fn get_stream(&self, session_id: &str, last_event_id: Option<&str>) -> reqwest::Result<reqwest::blocking::Response> {
+        let mut request = self.http.get(&self.url)
+            .header(ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, session_id);
+        if let Some(last_event_id) = last_event_id {
+            request = request.header("Last-Event-ID", last_event_id);
+        }
+        request.send()
+    }
+
+    /// Forward stream events inbound, resuming with `Last-Event-ID` when the
+    /// connection breaks before the server ends the stream.
+    fn consume_stream(&self, shared: &HttpShared, mut reader: SseReader<BufReader<reqwest::blocking::Response>>, session_id: &str) {
+        let mut last_event_id: Option<String> = None;
+        let mut attempts = 0;
+        loop {
+            if shared.closed.load(Ordering::Acquire) {
+                return;
+            }
+            let error = match reader.next_event() {
+                Ok(Some(event)) => {
+                    attempts = 0;
+                    if event.id.is_some() {
+                        last_event_id = event.id.clone();
+                    }
+                    shared.push_inbound(session_id, event.data);
+                    continue;
+                }
+                Ok(None) => return,
+                Err(e) => e,
+            };
+
+            let last = match &last_event_id {
+                Some(last) if attempts < RECONNECT_ATTEMPTS => last,
+                _ => {
+                    warn!("Http stream broken: {}", error);
+                    return;
+                }
+            };
+            attempts += 1;
+            std::thread::sleep(RECONNECT_DELAY * attempts);
+            info!("Resuming http stream after event {}", last);
+            match self.get_stream(session_id, Some(last)) {
+                Ok(response) if response.status().is_success() => {
+                    reader = SseReader::new(BufReader::new(response));
+                }
+                Ok(response) => {
+                    warn!("Server refused to resume stream: {}", response.status());
+                    return;
+                }
+                Err(e) => warn!("Failed to resume http stream: {}", e),
+            }
+        }
     }
 }
 
+impl HttpShared {
+    fn push_inbound(&self, session_id: &str, data: String) {
+        let _ = self.inbound.send(PayLoad {
+            data: Some(data),
+            ctx: Some(session_ctx(session_id)),
+        });
+    }
+}
 
 impl McpLayer for HttpStreamTransport {
     fn create(&self) -> SharedLayer {
-        let io = self.clone();
-        let tx_io = io.clone();
-        let rx_io = io.clone();
+        let tx_io = self.clone();
+        let rx_io = self.clone();
 
         let builder = LayerBuilder::new();
         let layer = builder
-            .with_inbound_fn(move |req|{
-                let data = rx_io.layer0_rx();
-                return  Ok(LayerResult{
+            .with_inbound_fn(move |_req|{
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult{
                     direction: Direction::Inbound,
-                    data: Some(data.unwrap()),
+                    data: Some(data),
                 })
             })
             .with_outbound_fn(move |req|{
-                if req.is_none(){
-                    return Err("no data to send".to_string());
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("http transport failed to send: {}", e);
+                    return Err(e.to_string());
                 }
-                let req = req.unwrap();
-                tx_io.layer0_tx(req).unwrap();
                 Ok(LayerResult{
                     direction: Direction::Outbound,
                     data: None,
                 })
             }).build();
-        return layer.unwrap();    
+        layer.unwrap()
     }
 }
 
+fn new_session_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn session_ctx(session_id: &str) -> ChainContext {
+    let mut ctx = ChainContext {
+        data: HashMap::new(),
+    };
+    ctx.data.insert(SESSION_ID_KEY.to_owned(), session_id.to_string());
+    ctx
+}
+
+/// Split an event id of the form `<stream>-<seq>`.
+fn parse_event_id(id: &str) -> Option<(u64, u64)> {
+    let (stream, seq) = id.split_once('-')?;
+    Some((stream.parse().ok()?, seq.parse().ok()?))
+}
+
+fn session_header(session_id: &str) -> Header {
+    Header::from_bytes(SESSION_HEADER.as_bytes(), session_id.as_bytes()).unwrap()
+}
+
+fn header_value(request: &Request, name: &str) -> Option<String> {
+    request.headers().iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn accepts(request: &Request, mime: &str) -> bool {
+    header_value(request, "Accept")
+        .map(|accept| accept.contains(mime) || accept.contains("*/*"))
+        .unwrap_or(false)
+}
+
+fn respond_status(request: Request, code: u16, message: &str) {
+    let _ = request.respond(Response::from_string(message).with_status_code(code));
+}
+
+fn respond_error(request: Request, status: u16, code: i32, message: &str) {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": code, "message": message },
+    });
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap());
+    let _ = request.respond(response);
+}
+
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
     use crate::config::transport_config::HttpTransportConfig;
+    use crate::client::{Client, ClientProvider};
+    use crate::executor::{ClientExecutor, ServerExecutor};
+    use crate::schema::schema::LATEST_PROTOCOL_VERSION;
+    use crate::server::{Server, ServerConfig};
 
     use super::*;
 
@@ -262,8 +1064,337 @@ mod tests {
             enable_tls: false,
             cert_file: None,
             key_file: None,
+            ..Default::default()
         }, true);
 
         server.start().unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_http_stream_session() {
+        let transport = HttpStreamTransport::new(HttpTransportConfig {
+            port: 0,
+            ..Default::default()
+        }, true);
+        let url = format!("http://{}/mcp", transport.local_addr().unwrap());
+        transport.start().unwrap();
+
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut executor = ServerExecutor::new();
+        let _ = executor.start(server);
+
+        let client = reqwest::blocking::Client::new();
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" }
+            }
+        });
+        let resp = client.post(&url)
+            .header("Accept", "application/json")
+            .json(&initialize)
+            .send().unwrap();
+        assert_eq!(resp.status(), 200);
+        let session_id = resp.headers().get(SESSION_HEADER).unwrap().to_str().unwrap().to_string();
+        let body: Value = resp.json().unwrap();
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["result"]["protocolVersion"], "2025-03-26");
+
+        //requests outside a session are rejected
+        let ping = json!({"jsonrpc": "2.0", "id": 2, "method": "ping"});
+        let resp = client.post(&url).header("Accept", "application/json").json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 400);
+        let resp = client.post(&url).header(SESSION_HEADER, "unknown").json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 404);
+
+        let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        let resp = client.post(&url).header(SESSION_HEADER, &session_id).json(&initialized).send().unwrap();
+        assert_eq!(resp.status(), 202);
+
+        //answered over SSE when the client accepts it
+        let resp = client.post(&url)
+            .header("Accept", "application/json, text/event-stream")
+            .header(SESSION_HEADER, &session_id)
+            .json(&ping)
+            .send().unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/event-stream");
+        let body = resp.text().unwrap();
+        let data = body.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+        let message: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(message["id"], 2);
+
+        let resp = client.delete(&url).header(SESSION_HEADER, &session_id).send().unwrap();
+        assert_eq!(resp.status(), 200);
+        let resp = client.post(&url).header(SESSION_HEADER, &session_id).json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 404);
+
+        executor.stop();
+    }
+
+    #[test]
+    fn test_http_stream_limits() {
+        let transport = HttpStreamTransport::new(HttpTransportConfig {
+            port: 0,
+            session_idle_timeout: 1,
+            max_concurrent_requests: 1,
+            ..Default::default()
+        }, true);
+        let addr = transport.local_addr().unwrap();
+        let url = format!("http://{}/mcp", addr);
+        transport.start().unwrap();
+
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut executor = ServerExecutor::new();
+        let _ = executor.start(server);
+
+        let in_flight = |count: usize| {
+            let start = Instant::now();
+            while transport.shared.active_requests.load(Ordering::Acquire) != count && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(transport.shared.active_requests.load(Ordering::Acquire), count);
+        };
+
+        let client = reqwest::blocking::Client::new();
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let resp = client.post(&url).header("Accept", "application/json").json(&initialize).send().unwrap();
+        assert_eq!(resp.status(), 200);
+        let session_id = resp.headers().get(SESSION_HEADER).unwrap().to_str().unwrap().to_string();
+        in_flight(0);
+
+        //a request still waiting for its body holds the only handler
+        let mut stuck = std::net::TcpStream::connect(addr).unwrap();
+        write!(stuck, "POST /mcp HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: 100000\r\n\r\n", addr).unwrap();
+        in_flight(1);
+        let ping = json!({"jsonrpc": "2.0", "id": 2, "method": "ping"});
+        let resp = client.post(&url).header("Accept", "application/json").header(SESSION_HEADER, &session_id).json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 503);
+
+        drop(stuck);
+        in_flight(0);
+        let resp = client.post(&url).header("Accept", "application/json").header(SESSION_HEADER, &session_id).json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 200);
+
+        //left alone, the session goes away with its replay buffer
+        std::thread::sleep(Duration::from_millis(2500));
+        assert!(transport.shared.sessions.is_empty());
+        let resp = client.post(&url).header("Accept", "application/json").header(SESSION_HEADER, &session_id).json(&ping).send().unwrap();
+        assert_eq!(resp.status(), 404);
+
+        executor.stop();
+    }
+
+    #[test]
+    fn test_http_legacy_sse() {
+        let transport = HttpStreamTransport::new(HttpTransportConfig {
+            port: 0,
+            legacy_sse: true,
+            ..Default::default()
+        }, true);
+        let base = format!("http://{}", transport.local_addr().unwrap());
+        transport.start().unwrap();
+
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut executor = ServerExecutor::new();
+        let _ = executor.start(server);
+
+        let client = reqwest::blocking::Client::new();
+        let resp = client.get(format!("{}/sse", base)).header("Accept", "text/event-stream").send().unwrap();
+        assert_eq!(resp.status(), 200);
+        let mut events = SseReader::new(std::io::BufReader::new(resp));
+        let endpoint = events.next_event().unwrap().unwrap();
+        assert_eq!(endpoint.event.as_deref(), Some("endpoint"));
+        assert!(endpoint.data.starts_with("/messages?sessionId="));
+
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "legacy", "version": "1.0" }
+            }
+        });
+        let resp = client.post(format!("{}{}", base, endpoint.data)).json(&initialize).send().unwrap();
+        assert_eq!(resp.status(), 202);
+        let response: Value = serde_json::from_str(&events.next_event().unwrap().unwrap().data).unwrap();
+        assert_eq!(response["id"], 1);
+        assert!(response["result"]["serverInfo"].is_object());
+
+        let resp = client.post(format!("{}/messages?sessionId=unknown", base)).json(&initialize).send().unwrap();
+        assert_eq!(resp.status(), 404);
+
+        //streamable clients share the same server
+        let resp = client.post(format!("{}/mcp", base))
+            .header("Accept", "application/json")
+            .json(&initialize)
+            .send().unwrap();
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get(SESSION_HEADER).is_some());
+
+        executor.stop();
+    }
+
+    fn reply(transport: &HttpStreamTransport, request: &PayLoad, data: Value) {
+        transport.layer0_tx(PayLoad {
+            data: Some(data.to_string()),
+            ctx: request.ctx.clone(),
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_http_stream_resume() {
+        let transport = HttpStreamTransport::new(HttpTransportConfig {
+            port: 0,
+            ..Default::default()
+        }, true);
+        let addr = transport.local_addr().unwrap();
+        let url = format!("http://{}/mcp", addr);
+        transport.start().unwrap();
+
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let init_url = url.clone();
+        let init = std::thread::spawn(move || {
+            reqwest::blocking::Client::new().post(&init_url)
+                .header("Accept", "application/json")
+                .json(&initialize)
+                .send().unwrap()
+        });
+        let request = transport.layer0_rx().unwrap();
+        reply(&transport, &request, json!({"jsonrpc": "2.0", "id": 1, "result": {}}));
+        let session_id = init.join().unwrap().headers().get(SESSION_HEADER).unwrap().to_str().unwrap().to_string();
+
+        //long running call over a connection that is about to drop
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "slow"}}).to_string();
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /mcp HTTP/1.1\r\nHost: {}\r\nAccept: text/event-stream\r\n{}: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            addr, SESSION_HEADER, session_id, call.len(), call).unwrap();
+        let request = transport.layer0_rx().unwrap();
+
+        let progress = |n: u64| json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progressToken": 2, "progress": n}});
+        reply(&transport, &request, progress(1));
+        let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+        let last_event_id = loop {
+            let mut line = String::new();
+            std::io::BufRead::read_line(&mut reader, &mut line).unwrap();
+            if let Some(id) = line.trim().strip_prefix("id: ") {
+                break id.to_string();
+            }
+        };
+        stream.shutdown(std::net::Shutdown::Both).unwrap();
+        drop(reader);
+
+        //emitted while disconnected, replayed on resume
+        reply(&transport, &request, progress(2));
+
+        let standalone = reqwest::blocking::Client::new().get(&url)
+            .header("Accept", "text/event-stream")
+            .header(SESSION_HEADER, &session_id)
+            .send().unwrap();
+        assert_eq!(standalone.status(), 200);
+
+        let resp = reqwest::blocking::Client::new().get(&url)
+            .header("Accept", "text/event-stream")
+            .header(SESSION_HEADER, &session_id)
+            .header("Last-Event-ID", &last_event_id)
+            .send().unwrap();
+        assert_eq!(resp.status(), 200);
+
+        //still pending, delivered live on the resumed stream
+        reply(&transport, &request, json!({"jsonrpc": "2.0", "id": 2, "result": {"content": []}}));
+
+        let mut events = SseReader::new(std::io::BufReader::new(resp));
+        let missed = events.next_event().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&missed.data).unwrap()["params"]["progress"], 2);
+        let result = events.next_event().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&result.data).unwrap()["id"], 2);
+        assert_eq!(parse_event_id(&result.id.unwrap()).unwrap().0, parse_event_id(&last_event_id).unwrap().0);
+        //nothing left pending on the resumed stream
+        assert!(events.next_event().unwrap().is_none());
+
+        //server initiated messages still go to the standalone stream
+        reply(&transport, &request, json!({"jsonrpc": "2.0", "method": "notifications/tools/list_changed"}));
+        let mut events = SseReader::new(std::io::BufReader::new(standalone));
+        let notification = events.next_event().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&notification.data).unwrap()["method"], "notifications/tools/list_changed");
+    }
+
+    #[derive(Clone, Default)]
+    struct NoopClientService;
+
+    impl ClientProvider for NoopClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_http_stream_client() {
+        let transport = HttpStreamTransport::new(HttpTransportConfig {
+            port: 0,
+            ..Default::default()
+        }, true);
+        let port = transport.local_addr().unwrap().port();
+        transport.start().unwrap();
+
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut server_executor = ServerExecutor::new();
+        let _ = server_executor.start(server);
+
+        let client_transport = HttpStreamTransport::new(HttpTransportConfig {
+            port,
+            ..Default::default()
+        }, false);
+        let mut client = Client::<NoopClientService>::new();
+        let client = client.with_timeout(Duration::from_secs(5));
+        client.add_transport_layer(client_transport.create());
+        client.start().unwrap();
+        client.build();
+        let mut client_executor = ClientExecutor::new();
+        let _ = client_executor.start(client.clone());
+
+        let init_result = client.initialize().unwrap();
+        assert_eq!(init_result["protocolVersion"], LATEST_PROTOCOL_VERSION);
+        assert!(client_transport.session_id().is_some());
+
+        client.ping().unwrap();
+        let tools = client.list_tool(None).unwrap();
+        assert!(tools.tools.is_empty());
+
+        client_transport.terminate_session().unwrap();
+        assert!(client_transport.session_id().is_none());
+
+        client_executor.stop();
+        server_executor.stop();
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use log::warn;
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+
+use crate::schema::schema::SESSION_ID_KEY;
+use crate::support::definition::McpLayer;
+use crate::MCPError;
+
+/// One end of an in-process connection. Payloads are moved through a
+/// channel as they are, nothing is serialized or written to disk.
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    tx: Sender<PayLoad>,
+    rx: Receiver<PayLoad>,
+    closed: Arc<AtomicBool>,
+    session_id: String,
+}
+
+impl LoopbackTransport {
+    /// Connected (server, client) ends.
+    pub fn pair() -> (Self, Self) {
+        let (server_tx, client_rx) = unbounded();
+        let (client_tx, server_rx) = unbounded();
+        let closed = Arc::new(AtomicBool::new(false));
+        let session_id = "loopback".to_string();
+
+        let server = LoopbackTransport {
+            tx: server_tx,
+            rx: server_rx,
+            closed: closed.clone(),
+            session_id: session_id.clone(),
+        };
+        let client = LoopbackTransport {
+            tx: client_tx,
+            rx: client_rx,
+            closed,
+            session_id,
+        };
+        (server, client)
+    }
+
+    pub fn with_session_id(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+
+    /// Close both ends, pending receives fail once drained.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        if self.is_closed() {
+            return Err(MCPError::Closed("loopback".to_string()));
+        }
+        self.tx.send(data)
+            .map_err(|_| MCPError::Closed("loopback".to_string()))
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        loop {
+            match self.rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(mut payload) => {
+                    let ctx = payload.ctx.get_or_insert_with(|| ChainContext {
+                        data: HashMap::new(),
+                    });
+                    ctx.data.insert(SESSION_ID_KEY.to_owned(), self.session_id.clone());
+                    return Ok(payload);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.is_closed() {
+                        return Err(MCPError::Closed("loopback".to_string()));
+                    }
+                }
+                //peer dropped without close(), nothing more can arrive
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(MCPError::Closed("loopback".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl McpLayer for LoopbackTransport {
+    fn create(&self) -> SharedLayer {
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("loopback transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_transport() {
+        let (server, client) = LoopbackTransport::pair();
+        let server_layer = server.create();
+        let client_layer = client.create();
+
+        let request = PayLoad {
+            data: Some("{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}".to_string()),
+            ctx: None,
+        };
+        client_layer.borrow().handle_outbound(Some(request)).unwrap();
+        let received = server_layer.borrow().handle_inbound(None).unwrap().data.unwrap();
+        assert!(received.data.unwrap().contains("ping"));
+        assert_eq!(received.ctx.unwrap().data.get(SESSION_ID_KEY).unwrap(), "loopback");
+
+        let response = PayLoad {
+            data: Some("{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}".to_string()),
+            ctx: None,
+        };
+        server_layer.borrow().handle_outbound(Some(response)).unwrap();
+        assert!(client.layer0_rx().unwrap().data.unwrap().contains("result"));
+
+        server.close();
+        assert!(client.layer0_rx().is_err());
+        assert!(client.layer0_tx(PayLoad { data: None, ctx: None }).is_err());
+    }
+
+    #[test]
+    fn test_loopback_peer_dropped() {
+        let (server, client) = LoopbackTransport::pair();
+        drop(server);
+        assert!(matches!(client.layer0_rx(), Err(MCPError::Closed(_))));
+        assert!(matches!(
+            client.layer0_tx(PayLoad { data: None, ctx: None }),
+            Err(MCPError::Closed(_))
+        ));
+    }
+}
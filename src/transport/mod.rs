@@ -17,3 +17,42 @@
 pub mod stdio;
 pub mod trace;
 pub mod httpstream;
+pub mod stdstream;
+pub mod sse;
+pub mod websocket;
+pub mod socket;
+pub mod child;
+pub mod loopback;
+pub mod shmem;
+pub mod framing;
+
+use std::cell::Cell;
+
+use crate::MCPError;
+
+thread_local! {
+    //layer errors reach the caller as a generic message, so a closed
+    //transport is flagged next to it for the thread driving the read
+    static CLOSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Turn a transport read error into a layer error, remembering a closed transport.
+pub(crate) fn inbound_error(e: MCPError) -> String {
+    if matches!(e, MCPError::Closed(_)) {
+        CLOSED.with(|closed| closed.set(true));
+    }
+    e.to_string()
+}
+
+/// Run a read through the layer chain, telling a closed transport apart
+/// from any other failure along the chain.
+pub(crate) fn read_inbound<T>(read: impl FnOnce() -> Result<T, String>) -> Result<T, MCPError> {
+    CLOSED.with(|closed| closed.set(false));
+    read().map_err(|message| {
+        if CLOSED.with(|closed| closed.replace(false)) {
+            MCPError::Closed(message)
+        } else {
+            MCPError::Transport(message)
+        }
+    })
+}
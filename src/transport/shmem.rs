@@ -0,0 +1,427 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use dashmap::DashMap;
+use disruptor::Producer;
+use log::{info, warn};
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+
+use crate::schema::schema::SESSION_ID_KEY;
+use crate::support::definition::McpLayer;
+use crate::support::sessons::SESSION_STORE;
+use crate::support::shared_memory::{ControlSegment, MemoryDuplex, SharedMemory, SharedMemoryError};
+use crate::support::ControlBus;
+use crate::MCPError;
+
+/// Size of each per-client ring.
+const RING_CAPACITY: usize = 128000;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a client waits for the server to hand out its rings.
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a send waits for the peer to drain its ring.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// State shared between the accept loop, per-client readers and the layer.
+struct ShmShared {
+    is_server: bool,
+    connections: DashMap<String, Arc<Mutex<SharedMemory>>>,
+    inbound: Sender<PayLoad>,
+    closed: AtomicBool,
+    /// Control segment owned by a server, removed on shutdown.
+    control_path: Option<PathBuf>,
+}
+
+/// Stops the accept loop and the readers once the last transport clone goes
+/// away. A client says goodbye so the server can release its rings.
+struct StopGuard {
+    control_bus: Arc<ControlBus>,
+    shared: Arc<ShmShared>,
+}
+
+impl StopGuard {
+    fn stop(&self) {
+        if self.shared.closed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        if let Ok(mut tx) = self.control_bus.clone_tx() {
+            tx.publish(|e| {
+                *e = 1;
+            });
+        }
+        if let Some(path) = &self.shared.control_path {
+            let _ = std::fs::remove_file(path);
+        }
+        if !self.shared.is_server {
+            for connection in self.shared.connections.iter() {
+                if let Ok(writer) = connection.value().lock() {
+                    //the server drains what we sent and then sees us gone
+                    writer.close();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Shared memory transport serving any number of local clients. Clients
+/// register through a control segment at `<path>_control` and get a
+/// dedicated ring pair at `<path>_<client id>`, the client id doubling as
+/// the session id on the server.
+#[derive(Clone)]
+pub struct SharedMemoryTransport {
+    control_bus: Arc<ControlBus>,
+    is_server: bool,
+    path: PathBuf,
+    control: Option<Arc<Mutex<Option<ControlSegment>>>>,
+    shared: Arc<ShmShared>,
+    inbound_rx: Receiver<PayLoad>,
+    guard: Arc<StopGuard>,
+}
+
+impl SharedMemoryTransport {
+    pub fn new(path: impl AsRef<Path>, is_server: bool) -> Result<Self, MCPError> {
+        let path = path.as_ref().to_path_buf();
+        let control = if is_server {
+            let control = ControlSegment::create(control_path(&path))
+                .map_err(|e| MCPError::Transport(format!("Failed to create shared memory control segment: {}", e)))?;
+            Some(Arc::new(Mutex::new(Some(control))))
+        } else {
+            None
+        };
+
+        let (inbound, inbound_rx) = unbounded();
+        let shared = Arc::new(ShmShared {
+            is_server,
+            connections: DashMap::new(),
+            inbound,
+            closed: AtomicBool::new(false),
+            control_path: Some(control_path(&path)).filter(|_| is_server),
+        });
+
+        let control_bus = Arc::new(ControlBus::new());
+        Ok(SharedMemoryTransport {
+            control_bus: control_bus.clone(),
+            is_server,
+            path,
+            control,
+            shared: shared.clone(),
+            inbound_rx,
+            guard: Arc::new(StopGuard { control_bus, shared }),
+        })
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// Number of registered clients.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.len()
+    }
+
+    /// Stop serving. A client tells the server it is leaving, pending
+    /// receives fail once drained.
+    pub fn close(&self) {
+        self.guard.stop();
+    }
+
+    /// Accept registrations on the server, register with the server on the client.
+    pub fn start(&self) -> Result<JoinHandle<()>, MCPError> {
+        match &self.control {
+            Some(control) => {
+                let control = control.lock()
+                    .map_err(|_| MCPError::Transport("Failed to lock control segment".to_string()))?
+                    .take()
+                    .ok_or_else(|| MCPError::Transport("Shared memory transport already started".to_string()))?;
+                self.accept_loop(control)
+            }
+            None => self.connect(),
+        }
+    }
+
+    fn accept_loop(&self, control: ControlSegment) -> Result<JoinHandle<()>, MCPError> {
+        let mut rx = self.control_bus.clone_rx()?;
+        let shared = self.shared.clone();
+        let path = self.path.clone();
+
+        let handle = std::thread::spawn(move || {
+            loop {
+                if rx.try_recv().is_ok() {
+                    break;
+                }
+                control.accept(Some(POLL_INTERVAL), |client_id| {
+                    if shared.connections.contains_key(client_id) {
+                        warn!("Shared memory client {} is already registered", client_id);
+                        return false;
+                    }
+                    let duplex = match MemoryDuplex::create(ring_path(&path, client_id), RING_CAPACITY) {
+                        Ok(duplex) => duplex,
+                        Err(e) => {
+                            warn!("Failed to create rings for {}: {}", client_id, e);
+                            return false;
+                        }
+                    };
+                    info!("Shared memory session {} connected", client_id);
+                    shared.serve_client(duplex, client_id.to_string());
+                    true
+                });
+            }
+        });
+
+        Ok(handle)
+    }
+
+    fn connect(&self) -> Result<JoinHandle<()>, MCPError> {
+        let shm_error = |e: SharedMemoryError| MCPError::Transport(format!("Failed to register with shared memory server: {}", e));
+        let client_id = format!("{:032x}", rand::random::<u128>());
+
+        let control = ControlSegment::open(control_path(&self.path)).map_err(shm_error)?;
+        control.register(&client_id, REGISTER_TIMEOUT).map_err(shm_error)?;
+        //the server creates the rings before accepting us
+        let duplex = MemoryDuplex::open(ring_path(&self.path, &client_id)).map_err(shm_error)?;
+        Ok(self.shared.serve_client(duplex, client_id))
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        let session_id = data.ctx.as_ref()
+            .and_then(|ctx| ctx.data.get(SESSION_ID_KEY).cloned());
+        let data = data.data.ok_or_else(||
+            MCPError::Transport("Payload data is None".to_string()))?;
+
+        let connection = match session_id.and_then(|sid| self.shared.connections.get(&sid)) {
+            Some(connection) => Some(connection.value().clone()),
+            //a client has a single ring pair
+            None if !self.is_server => self.shared.connections.iter().next().map(|c| c.value().clone()),
+            None => None,
+        };
+        let connection = connection.ok_or_else(||
+            MCPError::Transport("No shared memory client for session".to_string()))?;
+
+        let writer = connection.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock shared memory".to_string()))?;
+        writer.write_message(data.as_bytes(), Some(SEND_TIMEOUT))
+            .map_err(|e| MCPError::Transport(format!("Failed to write to shared memory: {}", e)))
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        loop {
+            match self.inbound_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(payload) => return Ok(payload),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shared.closed.load(Ordering::Acquire) {
+                        return Err(MCPError::Closed("shared memory".to_string()));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(MCPError::Closed("shared memory".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl ShmShared {
+    /// Register the client's writer for outbound routing and read frames
+    /// from its ring on a dedicated thread until it leaves.
+    fn serve_client(self: &Arc<Self>, duplex: MemoryDuplex, session_id: String) -> JoinHandle<()> {
+        let (reader, writer) = duplex.into_parts();
+        self.connections.insert(session_id.clone(), Arc::new(Mutex::new(writer)));
+
+        let shared = self.clone();
+        std::thread::spawn(move || {
+            loop {
+                match reader.read_message(Some(POLL_INTERVAL)) {
+                    Ok(data) => match String::from_utf8(data) {
+                        Ok(data) => shared.push_inbound(&session_id, data),
+                        Err(_) => warn!("Shared memory session {} sent invalid utf-8", session_id),
+                    },
+                    Err(SharedMemoryError::Timeout) => {
+                        if shared.closed.load(Ordering::Acquire) {
+                            break;
+                        }
+                    }
+                    //the peer closed the ring or its process died
+                    Err(SharedMemoryError::PeerGone) => break,
+                    Err(e) => {
+                        warn!("Shared memory session {} failed: {}", session_id, e);
+                        break;
+                    }
+                }
+            }
+
+            //dropping the rings on the server removes their files
+            shared.connections.remove(&session_id);
+            if shared.is_server {
+                SESSION_STORE.invalidate_session(&session_id);
+            } else {
+                shared.closed.store(true, Ordering::Release);
+            }
+            info!("Shared memory session {} closed", session_id);
+        })
+    }
+
+    fn push_inbound(&self, session_id: &str, data: String) {
+        let mut ctx = ChainContext {
+            data: HashMap::new(),
+        };
+        ctx.data.insert(SESSION_ID_KEY.to_owned(), session_id.to_string());
+        let _ = self.inbound.send(PayLoad {
+            data: Some(data),
+            ctx: Some(ctx),
+        });
+    }
+}
+
+impl McpLayer for SharedMemoryTransport {
+    fn create(&self) -> SharedLayer {
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("shared memory transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+fn control_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push("_control");
+    PathBuf::from(path)
+}
+
+fn ring_path(path: &Path, client_id: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!("_{}", client_id));
+    PathBuf::from(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::client::{Client, ClientProvider};
+    use crate::executor::{ClientExecutor, ServerExecutor};
+    use crate::schema::schema::{RequestId, LATEST_PROTOCOL_VERSION};
+    use crate::server::{Server, ServerConfig};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct NoopClientService;
+
+    impl ClientProvider for NoopClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shared_memory_transport_clients() {
+        let path = std::env::temp_dir().join(format!("mcps-shm-{:x}", rand::random::<u64>()));
+        assert!(SharedMemoryTransport::new("/dev/null/mcps-shm", true).is_err());
+        let transport = SharedMemoryTransport::new(&path, true).unwrap();
+        transport.start().unwrap();
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut server_executor = ServerExecutor::new();
+        let _ = server_executor.start(server);
+
+        let mut clients: Vec<_> = (0..2).map(|_| {
+            let client_transport = SharedMemoryTransport::new(&path, false).unwrap();
+            client_transport.start().unwrap();
+            let mut client = Client::<NoopClientService>::new();
+            let client = client.with_timeout(Duration::from_secs(5));
+            client.add_transport_layer(client_transport.create());
+            client.start().unwrap();
+            client.build();
+            let mut client_executor = ClientExecutor::new();
+            let _ = client_executor.start(client.clone());
+            (client.clone(), client_executor, client_transport)
+        }).collect();
+        assert_eq!(transport.connections(), 2);
+
+        for (client, _, _) in &mut clients {
+            let init_result = client.initialize().unwrap();
+            assert_eq!(init_result["protocolVersion"], LATEST_PROTOCOL_VERSION);
+            client.ping().unwrap();
+        }
+
+        //a leaving client releases its rings on the server
+        for (_, client_executor, client_transport) in &clients {
+            client_transport.close();
+            client_executor.stop();
+        }
+        let start = std::time::Instant::now();
+        while transport.connections() > 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(transport.connections(), 0);
+
+        transport.close();
+        server_executor.stop();
+        assert!(!control_path(&path).exists());
+    }
+}
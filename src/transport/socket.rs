@@ -0,0 +1,564 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use dashmap::DashMap;
+use disruptor::Producer;
+use log::{info, warn};
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+
+use crate::config::transport_config::{Framing, HttpTransportConfig, TransportType};
+use crate::schema::schema::SESSION_ID_KEY;
+use crate::support::definition::McpLayer;
+use crate::support::sessons::SESSION_STORE;
+use crate::transport::framing::{encode_frame, FrameDecoder};
+use crate::support::ControlBus;
+use crate::MCPError;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+enum SocketStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl SocketStream {
+    fn try_clone(&self) -> std::io::Result<Self> {
+        match self {
+            SocketStream::Tcp(s) => s.try_clone().map(SocketStream::Tcp),
+            SocketStream::Unix(s) => s.try_clone().map(SocketStream::Unix),
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            SocketStream::Tcp(s) => s.set_nonblocking(nonblocking),
+            SocketStream::Unix(s) => s.set_nonblocking(nonblocking),
+        }
+    }
+
+    fn shutdown(&self) {
+        let _ = match self {
+            SocketStream::Tcp(s) => s.shutdown(Shutdown::Both),
+            SocketStream::Unix(s) => s.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for SocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SocketStream::Tcp(s) => s.read(buf),
+            SocketStream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for SocketStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            SocketStream::Tcp(s) => s.write(buf),
+            SocketStream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            SocketStream::Tcp(s) => s.flush(),
+            SocketStream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+enum SocketListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl SocketListener {
+    fn accept(&self) -> std::io::Result<SocketStream> {
+        match self {
+            SocketListener::Tcp(l) => l.accept().map(|(s, _)| SocketStream::Tcp(s)),
+            SocketListener::Unix(l) => l.accept().map(|(s, _)| SocketStream::Unix(s)),
+        }
+    }
+}
+
+/// State shared between the accept loop, connection readers and the layer.
+struct SocketShared {
+    is_server: bool,
+    framing: Framing,
+    connections: DashMap<String, Arc<Mutex<SocketStream>>>,
+    inbound: Sender<PayLoad>,
+    closed: AtomicBool,
+    /// Socket file owned by a unix server, removed on shutdown.
+    socket_path: Option<PathBuf>,
+}
+
+/// Stops the accept loop and hangs up every connection once the last
+/// transport clone goes away.
+struct StopGuard {
+    control_bus: Arc<ControlBus>,
+    shared: Arc<SocketShared>,
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Ok(mut tx) = self.control_bus.clone_tx() {
+            tx.publish(|e| {
+                *e = 1;
+            });
+        }
+        for connection in self.shared.connections.iter() {
+            if let Ok(stream) = connection.value().lock() {
+                stream.shutdown();
+            }
+        }
+        if let Some(path) = &self.shared.socket_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Raw TCP or unix domain socket transport. The server accepts any number of
+/// connections and tags every inbound payload with the connection's session.
+#[derive(Clone)]
+pub struct SocketTransport {
+    control_bus: Arc<ControlBus>,
+    is_server: bool,
+    config: HttpTransportConfig,
+    listener: Option<Arc<SocketListener>>,
+    shared: Arc<SocketShared>,
+    inbound_rx: Receiver<PayLoad>,
+    _guard: Arc<StopGuard>,
+}
+
+impl SocketTransport {
+    /// Build a `tcp` or `unix` transport as selected by `config.transport_type`.
+    pub fn new(config: HttpTransportConfig, is_server: bool) -> Self {
+        let socket_path = match config.transport_type {
+            TransportType::Tcp => None,
+            TransportType::Unix => Some(PathBuf::from(config.socket_path.clone()
+                .expect("Missing socket_path for unix transport"))),
+            other => panic!("Unsupported socket transport type {:?}", other),
+        };
+
+        let listener = if is_server {
+            let listener = match &socket_path {
+                Some(path) => {
+                    //a previous server may have left its socket file behind
+                    let _ = std::fs::remove_file(path);
+                    info!("Starting unix socket server on {}", path.display());
+                    let listener = UnixListener::bind(path)
+                        .expect("Failed to start unix socket server");
+                    listener.set_nonblocking(true)
+                        .expect("Failed to set unix listener non-blocking");
+                    SocketListener::Unix(listener)
+                }
+                None => {
+                    let bind_addr = format!("{}:{}", config.ip_address, config.port);
+                    info!("Starting tcp server on {}", bind_addr);
+                    let listener = TcpListener::bind(&bind_addr)
+                        .expect("Failed to start tcp server");
+                    listener.set_nonblocking(true)
+                        .expect("Failed to set tcp listener non-blocking");
+                    SocketListener::Tcp(listener)
+                }
+            };
+            Some(Arc::new(listener))
+        } else {
+            None
+        };
+
+        let (inbound, inbound_rx) = unbounded();
+        let shared = Arc::new(SocketShared {
+            is_server,
+            framing: config.framing,
+            connections: DashMap::new(),
+            inbound,
+            closed: AtomicBool::new(false),
+            socket_path: socket_path.filter(|_| is_server),
+        });
+
+        let control_bus = Arc::new(ControlBus::new());
+        SocketTransport {
+            control_bus: control_bus.clone(),
+            is_server,
+            config,
+            listener,
+            shared: shared.clone(),
+            inbound_rx,
+            _guard: Arc::new(StopGuard { control_bus, shared }),
+        }
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// Address a tcp server is listening on, useful when binding port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self.listener.as_deref() {
+            Some(SocketListener::Tcp(l)) => l.local_addr().ok(),
+            _ => None,
+        }
+    }
+
+    /// Number of open connections.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.len()
+    }
+
+    /// Accept connections on the server, connect to the remote socket on the client.
+    pub fn start(&self) -> Result<JoinHandle<()>, MCPError> {
+        match self.listener.clone() {
+            Some(listener) => self.accept_loop(listener),
+            None => self.connect(),
+        }
+    }
+
+    fn accept_loop(&self, listener: Arc<SocketListener>) -> Result<JoinHandle<()>, MCPError> {
+        let mut rx = self.control_bus.clone_rx()?;
+        let shared = self.shared.clone();
+
+        let handle = std::thread::spawn(move || {
+            loop {
+                if rx.try_recv().is_ok() {
+                    break;
+                }
+                match listener.accept() {
+                    Ok(stream) => {
+                        let session_id = format!("{:032x}", rand::random::<u128>());
+                        info!("Socket session {} connected", session_id);
+                        if let Err(e) = shared.serve_connection(stream, session_id) {
+                            warn!("Failed to serve socket connection: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(e) => warn!("Failed to accept socket connection: {}", e),
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    fn connect(&self) -> Result<JoinHandle<()>, MCPError> {
+        let stream = match &self.config.transport_type {
+            TransportType::Unix => {
+                let path = self.config.socket_path.clone().unwrap_or_default();
+                UnixStream::connect(&path)
+                    .map(SocketStream::Unix)
+                    .map_err(|e| MCPError::Transport(format!("Failed to connect to {}: {}", path, e)))?
+            }
+            _ => {
+                let addr = format!("{}:{}", self.config.ip_address, self.config.port);
+                TcpStream::connect(&addr)
+                    .map(SocketStream::Tcp)
+                    .map_err(|e| MCPError::Transport(format!("Failed to connect to {}: {}", addr, e)))?
+            }
+        };
+        self.shared.serve_connection(stream, "local".to_string())
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        let session_id = data.ctx.as_ref()
+            .and_then(|ctx| ctx.data.get(SESSION_ID_KEY).cloned());
+        let data = data.data.ok_or_else(||
+            MCPError::Transport("Payload data is None".to_string()))?;
+
+        let connection = match session_id.and_then(|sid| self.shared.connections.get(&sid)) {
+            Some(connection) => Some(connection.value().clone()),
+            //a client has a single connection
+            None if !self.is_server => self.shared.connections.iter().next().map(|c| c.value().clone()),
+            None => None,
+        };
+        let connection = connection.ok_or_else(||
+            MCPError::Transport("No socket connection for session".to_string()))?;
+
+        let mut stream = connection.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock socket".to_string()))?;
+        write_frame(&mut *stream, self.config.framing, &data)
+            .map_err(|e| MCPError::Transport(format!("Failed to write to socket: {}", e)))
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        loop {
+            match self.inbound_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(payload) => return Ok(payload),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shared.closed.load(Ordering::Acquire) {
+                        return Err(MCPError::Closed("socket".to_string()));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(MCPError::Closed("socket".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl SocketShared {
+    /// Register the connection for outbound routing and read frames from it
+    /// on a dedicated thread until the peer hangs up.
+    fn serve_connection(self: &Arc<Self>, stream: SocketStream, session_id: String) -> Result<JoinHandle<()>, MCPError> {
+        let io_error = |e: std::io::Error| MCPError::Transport(format!("Socket error: {}", e));
+        stream.set_nonblocking(false).map_err(io_error)?;
+        let reader = stream.try_clone().map_err(io_error)?;
+        self.connections.insert(session_id.clone(), Arc::new(Mutex::new(stream)));
+
+        let shared = self.clone();
+        let handle = std::thread::spawn(move || {
+            let mut reader = reader;
+            let mut decoder = FrameDecoder::new(shared.framing);
+            loop {
+                match read_frame(&mut reader, &mut decoder) {
+                    Ok(Some(data)) => shared.push_inbound(&session_id, data),
+                    Ok(None) => break,
+                    //the decoder skipped a malformed frame, the next one is fine
+                    Err(MCPError::Transport(e)) => warn!("Socket session {} dropped a frame: {}", session_id, e),
+                    Err(e) => {
+                        if !shared.closed.load(Ordering::Acquire) {
+                            warn!("Socket session {} failed: {}", session_id, e);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            shared.connections.remove(&session_id);
+            if shared.is_server {
+                SESSION_STORE.invalidate_session(&session_id);
+            } else {
+                shared.closed.store(true, Ordering::Release);
+            }
+            info!("Socket session {} closed", session_id);
+        });
+        Ok(handle)
+    }
+
+    fn push_inbound(&self, session_id: &str, data: String) {
+        let mut ctx = ChainContext {
+            data: HashMap::new(),
+        };
+        ctx.data.insert(SESSION_ID_KEY.to_owned(), session_id.to_string());
+        let _ = self.inbound.send(PayLoad {
+            data: Some(data),
+            ctx: Some(ctx),
+        });
+    }
+}
+
+impl McpLayer for SocketTransport {
+    fn create(&self) -> SharedLayer {
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("socket transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+/// Read the next message, `None` on a clean end of stream. Read errors and
+/// a stream that lost its frame boundaries come back as `MCPError::Closed`.
+fn read_frame(reader: &mut impl Read, decoder: &mut FrameDecoder) -> Result<Option<String>, MCPError> {
+    let mut chunk = [0u8; 8192];
+    loop {
+        if let Some(message) = decoder.next_message()? {
+            return Ok(Some(message));
+        }
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(None),
+            Ok(n) => decoder.push(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(MCPError::Closed(format!("socket: {}", e))),
+        }
+    }
+}
+
+fn write_frame(writer: &mut impl Write, framing: Framing, data: &str) -> std::io::Result<()> {
+    writer.write_all(&encode_frame(framing, data))?;
+    writer.flush()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use serde_json::{json, Value};
+
+    use crate::client::{Client, ClientProvider};
+    use crate::executor::{ClientExecutor, ServerExecutor};
+    use crate::schema::schema::{RequestId, LATEST_PROTOCOL_VERSION};
+    use crate::server::{Server, ServerConfig};
+
+    use super::*;
+
+    fn start_server(transport: &SocketTransport) -> ServerExecutor {
+        transport.start().unwrap();
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut executor = ServerExecutor::new();
+        let _ = executor.start(server);
+        executor
+    }
+
+    #[test]
+    fn test_tcp_transport_sessions() {
+        let transport = SocketTransport::new(HttpTransportConfig {
+            transport_type: TransportType::Tcp,
+            port: 0,
+            ..Default::default()
+        }, true);
+        let addr = transport.local_addr().unwrap();
+        let executor = start_server(&transport);
+
+        //each connection is answered on its own socket
+        let mut agents: Vec<_> = (0..3).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        for (i, agent) in agents.iter_mut().enumerate() {
+            let ping = json!({"jsonrpc": "2.0", "id": i, "method": "ping"});
+            writeln!(agent, "{}", ping).unwrap();
+        }
+        for (i, agent) in agents.iter().enumerate() {
+            let mut line = String::new();
+            BufReader::new(agent).read_line(&mut line).unwrap();
+            let response: Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(response["id"], i);
+        }
+        assert_eq!(transport.connections(), 3);
+
+        drop(agents);
+        let start = std::time::Instant::now();
+        while transport.connections() > 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        assert_eq!(transport.connections(), 0);
+        executor.stop();
+    }
+
+    #[derive(Clone, Default)]
+    struct NoopClientService;
+
+    impl ClientProvider for NoopClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_unix_transport_length_framing() {
+        let path = std::env::temp_dir().join(format!("mcps-{:x}.sock", rand::random::<u64>()));
+        let config = HttpTransportConfig {
+            transport_type: TransportType::Unix,
+            socket_path: Some(path.to_string_lossy().to_string()),
+            framing: Framing::Length,
+            ..Default::default()
+        };
+        let transport = SocketTransport::new(config.clone(), true);
+        let server_executor = start_server(&transport);
+
+        let client_transport = SocketTransport::new(config, false);
+        client_transport.start().unwrap();
+        let mut client = Client::<NoopClientService>::new();
+        let client = client.with_timeout(Duration::from_secs(5));
+        client.add_transport_layer(client_transport.create());
+        client.start().unwrap();
+        client.build();
+        let mut client_executor = ClientExecutor::new();
+        let _ = client_executor.start(client.clone());
+
+        let init_result = client.initialize().unwrap();
+        assert_eq!(init_result["protocolVersion"], LATEST_PROTOCOL_VERSION);
+        client.ping().unwrap();
+
+        client_executor.stop();
+        server_executor.stop();
+        drop(transport);
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, Framing::Length, "{\"id\":1}").unwrap();
+        write_frame(&mut buf, Framing::Length, "{\"id\":2}").unwrap();
+        let (mut reader, mut decoder) = (&buf[..], FrameDecoder::new(Framing::Length));
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap().unwrap(), "{\"id\":1}");
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap().unwrap(), "{\"id\":2}");
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap(), None);
+
+        let mut buf = b"\r\nContent-Length: -1\r\n\r\n".to_vec();
+        write_frame(&mut buf, Framing::ContentLength, "{\"id\":\"é\"}").unwrap();
+        assert!(buf.ends_with(b"Content-Length: 11\r\n\r\n{\"id\":\"\xc3\xa9\"}"));
+        let (mut reader, mut decoder) = (&buf[..], FrameDecoder::new(Framing::ContentLength));
+        assert!(matches!(read_frame(&mut reader, &mut decoder), Err(MCPError::Transport(_))));
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap().unwrap(), "{\"id\":\"é\"}");
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap(), None);
+
+        let mut buf = Vec::new();
+        write_frame(&mut buf, Framing::Newline, "{\n\"id\": 1\n}").unwrap();
+        let (mut reader, mut decoder) = (&buf[..], FrameDecoder::new(Framing::Newline));
+        assert_eq!(read_frame(&mut reader, &mut decoder).unwrap().unwrap(), "{\"id\":1}");
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::io::{BufRead, Write};
+
+/// A single server-sent event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+}
+
+impl SseEvent {
+    pub fn message(data: &str) -> Self {
+        SseEvent {
+            id: None,
+            event: Some("message".to_string()),
+            data: data.to_string(),
+        }
+    }
+
+    /// Encode the event in `text/event-stream` wire format.
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Some(id) = &self.id {
+            out.push_str(&format!("id: {}\n", id));
+        }
+        if let Some(event) = &self.event {
+            out.push_str(&format!("event: {}\n", event));
+        }
+        for line in self.data.split('\n') {
+            out.push_str(&format!("data: {}\n", line));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Writes an SSE body over a raw HTTP/1.1 connection using chunked encoding,
+/// flushing every event so the peer sees it immediately.
+pub struct SseWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl SseWriter {
+    pub fn start(mut writer: Box<dyn Write + Send>, headers: &[(&str, String)]) -> std::io::Result<Self> {
+        let mut head = String::from("HTTP/1.1 200 OK\r\n");
+        head.push_str("Content-Type: text/event-stream\r\n");
+        head.push_str("Cache-Control: no-cache\r\n");
+        head.push_str("Transfer-Encoding: chunked\r\n");
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        writer.write_all(head.as_bytes())?;
+        writer.flush()?;
+        Ok(SseWriter { writer })
+    }
+
+    pub fn send(&mut self, event: &SseEvent) -> std::io::Result<()> {
+        self.chunk(event.encode().as_bytes())
+    }
+
+    /// Comment line, used as keepalive to detect dead peers.
+    pub fn comment(&mut self, text: &str) -> std::io::Result<()> {
+        self.chunk(format!(": {}\n\n", text).as_bytes())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.write_all(b"0\r\n\r\n")?;
+        self.writer.flush()
+    }
+
+    fn chunk(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(format!("{:x}\r\n", data.len()).as_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(b"\r\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Incremental `text/event-stream` parser over a response body.
+pub struct SseReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> SseReader<R> {
+    pub fn new(reader: R) -> Self {
+        SseReader { reader }
+    }
+
+    /// Next dispatched event, `None` once the stream ends.
+    pub fn next_event(&mut self) -> std::io::Result<Option<SseEvent>> {
+        let mut event = SseEvent::default();
+        let mut data: Vec<String> = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            if line.is_empty() {
+                if data.is_empty() {
+                    //comments or keepalives only, keep reading
+                    event = SseEvent::default();
+                    continue;
+                }
+                event.data = data.join("\n");
+                return Ok(Some(event));
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "id" => event.id = Some(value.to_string()),
+                "event" => event.event = Some(value.to_string()),
+                "data" => data.push(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_sse_reader() {
+        let mut first = SseEvent::message("{\"id\":1}");
+        first.id = Some("7".to_string());
+        let stream = format!("{}: keepalive\n\n{}", first.encode(), "data: a\r\ndata: b\r\n\r\n");
+
+        let mut reader = SseReader::new(Cursor::new(stream.into_bytes()));
+        assert_eq!(reader.next_event().unwrap(), Some(first));
+        assert_eq!(reader.next_event().unwrap().unwrap().data, "a\nb");
+        assert_eq!(reader.next_event().unwrap(), None);
+    }
+}
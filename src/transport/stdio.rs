@@ -23,9 +23,10 @@ use std::collections::HashMap;
 use std::io::Read;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use crate::schema::schema::SESSION_ID_KEY;
 use crate::support::definition::McpLayer;
-use crate::support::shared_memory::{MemoryDuplex, SharedMemory};
+use crate::support::shared_memory::{MemoryDuplex, SharedMemory, SharedMemoryError};
 use crate::MCPError;
 use bytes::BufMut;
 use disruptor::{Producer, Sequence};
@@ -36,6 +37,9 @@ use crate::support::disruptor::{DisruptorProcessorCallback, DisruptorWriter};
 use crate::support::disruptor::DisruptorFactory;
 use ibuf::{MBuf, MPool};
 
+/// How long a send waits for the peer to drain the ring.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Standard IO transport
 #[derive(Clone)]
 pub struct StdioTransport{
@@ -66,30 +70,17 @@ impl StdioTransport {
         let mut data = data.data.clone().ok_or_else(|| 
             MCPError::Transport("Payload data is None".to_string()))?;
 
-        let mut buf = MBuf::with_capacity(1024);
-        let data = data.as_bytes();
-        let len = data.len() as u32;
-        buf.append(&len.to_be_bytes());
-        buf.append(data);
-
-        self.pipe.write(&buf)
+        //the ring frames messages itself and chunks the large ones
+        self.pipe.write_message(data.as_bytes(), Some(SEND_TIMEOUT))
             .map_err(|e| MCPError::Transport(format!("Failed to write to shared memory: {}", e)))?;    
         Ok(())
     }
 
     pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
-        let mut len_data = [0; 4];
-
-        //read len first
-        let bytes_read = self.pipe.read(&mut len_data)
-            .map_err(|e| MCPError::Transport(format!("Failed to read from shared memory: {}", e)))?;
-
-        let len = u32::from_be_bytes(len_data);
-        let mut data = vec![0; len as usize];
-
-        
-        let bytes_read = self.pipe.read(&mut data)
-            .map_err(|e| MCPError::Transport(format!("Failed to read from shared memory: {}", e)))?;
+        let data = self.pipe.read_message(None).map_err(|e| match e {
+            SharedMemoryError::PeerGone => MCPError::Closed("stdio".to_string()),
+            e => MCPError::Transport(format!("Failed to read from shared memory: {}", e)),
+        })?;
 
         let mut ctx = ChainContext{
             data: HashMap::new(),
@@ -115,10 +106,10 @@ impl McpLayer for StdioTransport {
         let builder = LayerBuilder::new();
         let layer = builder
             .with_inbound_fn(move |req|{
-                let data = rx_io.layer0_rx();
-                return  Ok(LayerResult{
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult{
                     direction: Direction::Inbound,
-                    data: Some(data.unwrap()),
+                    data: Some(data),
                 })
             })
             .with_outbound_fn(move |req|{
@@ -126,7 +117,7 @@ impl McpLayer for StdioTransport {
                     return Err("no data to send".to_string());
                 }
                 let req = req.unwrap();
-                tx_io.layer0_tx(req).unwrap();
+                tx_io.layer0_tx(req).map_err(|e| e.to_string())?;
                 Ok(LayerResult{
                     direction: Direction::Outbound,
                     data: None,
@@ -185,4 +176,16 @@ mod tests {
         client.join().unwrap();
         server.join().unwrap();
     }
+
+    #[test]
+    fn test_stdio_peer_gone() {
+        let path = std::env::temp_dir().join(format!("mcps-stdio-{:x}", rand::random::<u64>()));
+        let server_transport = StdioTransport::new(&path, true);
+        let layer = StdioTransport::new(&path, false).create();
+        drop(server_transport);
+
+        //a vanished peer ends the read instead of panicking the serving thread
+        let result = crate::transport::read_inbound(|| layer.borrow().handle_inbound(None));
+        assert!(matches!(result, Err(MCPError::Closed(_))));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,281 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+use serde_json::Value;
+
+use crate::schema::schema::SESSION_ID_KEY;
+use crate::support::definition::McpLayer;
+use crate::MCPError;
+
+type StreamReader = Arc<Mutex<Box<dyn BufRead + Send>>>;
+type StreamWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// MCP stdio transport as described by the spec: newline-delimited JSON-RPC
+/// read from stdin and written to stdout. Diagnostics must go to stderr
+/// (see `init_stderr_log`), stdout carries protocol messages only.
+#[derive(Clone)]
+pub struct StdStreamTransport {
+    reader: StreamReader,
+    writer: StreamWriter,
+    closed: Arc<AtomicBool>,
+    session_id: String,
+    raw: bool,
+    /// Tail of a raw read that ended inside a multi-byte character.
+    partial: Arc<Mutex<Vec<u8>>>,
+}
+
+impl StdStreamTransport {
+    pub fn new() -> Self {
+        Self::with_streams(std::io::stdin(), std::io::stdout())
+    }
+
+    /// Build the transport over arbitrary byte streams, e.g. a child process pipes.
+    pub fn with_streams(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        StdStreamTransport {
+            reader: Arc::new(Mutex::new(Box::new(BufReader::new(reader)))),
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            closed: Arc::new(AtomicBool::new(false)),
+            session_id: "local".to_string(),
+            raw: false,
+            partial: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn with_session_id(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+
+    /// Hand up text as it is read and write payloads out unchanged, leaving
+    /// message boundaries to a `FramingLayer` on top.
+    pub fn raw(mut self) -> Self {
+        self.raw = true;
+        self
+    }
+
+    /// Whether the peer closed its end of the stream.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        let data = data.data.ok_or_else(||
+            MCPError::Transport("Payload data is None".to_string()))?;
+
+        //messages must not contain embedded newlines
+        let line = if self.raw {
+            data
+        } else if data.contains('\n') {
+            let value: Value = serde_json::from_str(&data)?;
+            serde_json::to_string(&value)?
+        } else {
+            data
+        };
+
+        let mut writer = self.writer.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock stdout".to_string()))?;
+        writer.write_all(line.as_bytes())
+            .and_then(|_| if self.raw { Ok(()) } else { writer.write_all(b"\n") })
+            .and_then(|_| writer.flush())
+            .map_err(|e| MCPError::Transport(format!("Failed to write to stdout: {}", e)))
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        if self.is_closed() {
+            return Err(MCPError::Closed("stdin".to_string()));
+        }
+
+        let mut reader = self.reader.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock stdin".to_string()))?;
+        if self.raw {
+            return self.read_chunk(&mut **reader);
+        }
+
+        loop {
+            let mut line = String::new();
+            let n = reader.read_line(&mut line)
+                .map_err(|e| MCPError::Transport(format!("Failed to read from stdin: {}", e)))?;
+
+            if n == 0 {
+                info!("stdin reached EOF, closing transport");
+                self.closed.store(true, Ordering::Release);
+                return Err(MCPError::Closed("stdin".to_string()));
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            return Ok(self.payload(line.to_string()));
+        }
+    }
+
+    fn read_chunk(&self, reader: &mut dyn BufRead) -> Result<PayLoad, MCPError> {
+        let mut partial = self.partial.lock()
+            .map_err(|_| MCPError::Transport("Failed to lock stdin".to_string()))?;
+
+        loop {
+            //a multi-byte character may straddle two reads
+            let valid = match std::str::from_utf8(&partial) {
+                Ok(_) => partial.len(),
+                Err(e) if e.valid_up_to() > 0 || e.error_len().is_none() => e.valid_up_to(),
+                Err(e) => {
+                    //drop the bad bytes so the next read starts past them
+                    partial.drain(..e.error_len().unwrap_or(1));
+                    return Err(MCPError::Transport(format!("stdin is not valid utf-8: {}", e)));
+                }
+            };
+            if valid > 0 {
+                let text = String::from_utf8(partial.drain(..valid).collect())
+                    .map_err(|e| MCPError::Transport(format!("stdin is not valid utf-8: {}", e)))?;
+                return Ok(self.payload(text));
+            }
+
+            let buf = reader.fill_buf()
+                .map_err(|e| MCPError::Transport(format!("Failed to read from stdin: {}", e)))?;
+            if buf.is_empty() {
+                info!("stdin reached EOF, closing transport");
+                self.closed.store(true, Ordering::Release);
+                return Err(MCPError::Closed("stdin".to_string()));
+            }
+            let n = buf.len();
+            partial.extend_from_slice(buf);
+            reader.consume(n);
+        }
+    }
+
+    fn payload(&self, data: String) -> PayLoad {
+        let mut ctx = ChainContext {
+            data: HashMap::new(),
+        };
+        ctx.data.insert(SESSION_ID_KEY.to_owned(), self.session_id.clone());
+
+        PayLoad {
+            data: Some(data),
+            ctx: Some(ctx),
+        }
+    }
+}
+
+impl Default for StdStreamTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl McpLayer for StdStreamTransport {
+    fn create(&self) -> SharedLayer {
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("stdio transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_std_stream_transport() {
+        let input = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n\n{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n";
+        let output = SharedBuf::default();
+        let transport = StdStreamTransport::with_streams(Cursor::new(input.as_bytes().to_vec()), output.clone());
+
+        let layer = transport.create();
+        let result = layer.borrow().handle_inbound(None).unwrap();
+        let payload = result.data.unwrap();
+        assert_eq!(payload.data.unwrap(), "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}");
+        assert_eq!(payload.ctx.unwrap().data.get(SESSION_ID_KEY).unwrap(), "local");
+
+        //blank lines are skipped
+        let result = layer.borrow().handle_inbound(None).unwrap();
+        assert!(result.data.unwrap().data.unwrap().contains("notifications/initialized"));
+
+        //EOF closes the transport
+        let closed = crate::transport::read_inbound(|| layer.borrow().handle_inbound(None));
+        assert!(matches!(closed, Err(MCPError::Closed(_))));
+        assert!(transport.is_closed());
+
+        let data = PayLoad {
+            data: Some("{\n \"jsonrpc\": \"2.0\",\n \"id\": 1,\n \"result\": {}\n}".to_string()),
+            ctx: None,
+        };
+        layer.borrow().handle_outbound(Some(data)).unwrap();
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(written.ends_with('\n'));
+        assert_eq!(written.matches('\n').count(), 1);
+        let value: Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(value["id"], 1);
+    }
+
+    #[test]
+    fn test_std_stream_invalid_utf8() {
+        let input = b"{\"id\":\xff1}\n".to_vec();
+        let transport = StdStreamTransport::with_streams(Cursor::new(input), SharedBuf::default()).raw();
+
+        //text around the bad byte still comes through, the bad byte once as an error
+        assert_eq!(transport.layer0_rx().unwrap().data.unwrap(), "{\"id\":");
+        assert!(matches!(transport.layer0_rx(), Err(MCPError::Transport(_))));
+        assert_eq!(transport.layer0_rx().unwrap().data.unwrap(), "1}\n");
+        assert!(matches!(transport.layer0_rx(), Err(MCPError::Closed(_))));
+    }
+}
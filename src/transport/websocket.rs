@@ -0,0 +1,481 @@
+// Copyright (c) { props["inceptionYear"] } { props["copyrightOwner"] }
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use dashmap::DashMap;
+use disruptor::Producer;
+use log::{info, warn};
+use nbus::BusReader;
+use rioc::{ChainContext, Direction, LayerBuilder, LayerResult, PayLoad, SharedLayer};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::{HeaderValue, StatusCode};
+use tungstenite::{Bytes, Message, WebSocket};
+
+use crate::config::transport_config::HttpTransportConfig;
+use crate::schema::schema::SESSION_ID_KEY;
+use crate::support::definition::McpLayer;
+use crate::support::sessons::SESSION_STORE;
+use crate::support::ControlBus;
+use crate::transport::httpstream::SESSION_HEADER;
+use crate::MCPError;
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// State shared between the accept loop, connection threads and the layer.
+struct WsShared {
+    control_bus: Arc<ControlBus>,
+    is_server: bool,
+    config: HttpTransportConfig,
+    connections: DashMap<String, Sender<String>>,
+    inbound: Sender<PayLoad>,
+    closed: AtomicBool,
+}
+
+/// Signals every connection to close once the last transport clone goes away.
+struct StopGuard {
+    control_bus: Arc<ControlBus>,
+    shared: Arc<WsShared>,
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Ok(mut tx) = self.control_bus.clone_tx() {
+            tx.publish(|e| {
+                *e = 1;
+            });
+        }
+    }
+}
+
+/// WebSocket transport, one JSON-RPC message per text frame. The server
+/// accepts any number of connections, each one is its own session.
+#[derive(Clone)]
+pub struct WebSocketTransport {
+    control_bus: Arc<ControlBus>,
+    listener: Option<Arc<TcpListener>>,
+    shared: Arc<WsShared>,
+    inbound_rx: Receiver<PayLoad>,
+    _guard: Arc<StopGuard>,
+}
+
+impl WebSocketTransport {
+    pub fn new(config: HttpTransportConfig, is_server: bool) -> Self {
+        let listener = if is_server {
+            let bind_addr = format!("{}:{}", config.ip_address, config.port);
+            info!("Starting WebSocket server on {}", bind_addr);
+            let listener = TcpListener::bind(&bind_addr)
+                .expect("Failed to start websocket server");
+            listener.set_nonblocking(true)
+                .expect("Failed to set websocket listener non-blocking");
+            Some(Arc::new(listener))
+        } else {
+            None
+        };
+
+        let (inbound, inbound_rx) = unbounded();
+        let control_bus = Arc::new(ControlBus::new());
+        let shared = Arc::new(WsShared {
+            control_bus: control_bus.clone(),
+            is_server,
+            config,
+            connections: DashMap::new(),
+            inbound,
+            closed: AtomicBool::new(false),
+        });
+
+        WebSocketTransport {
+            control_bus: control_bus.clone(),
+            listener,
+            shared: shared.clone(),
+            inbound_rx,
+            _guard: Arc::new(StopGuard { control_bus, shared }),
+        }
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.shared.is_server
+    }
+
+    /// Address the server is listening on, useful when binding port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// Number of open connections.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.len()
+    }
+
+    /// Accept connections on the server, connect to the remote endpoint on the client.
+    pub fn start(&self) -> Result<JoinHandle<()>, MCPError> {
+        match self.listener.clone() {
+            Some(listener) => self.accept_loop(listener),
+            None => self.connect(),
+        }
+    }
+
+    /// Close every connection with a close frame and stop accepting new ones.
+    pub fn stop(&self) -> Result<(), MCPError> {
+        self.shared.closed.store(true, Ordering::Release);
+        let mut tx = self.control_bus.clone_tx()?;
+        tx.publish(|e| {
+            *e = 1;
+        });
+        Ok(())
+    }
+
+    fn accept_loop(&self, listener: Arc<TcpListener>) -> Result<JoinHandle<()>, MCPError> {
+        let mut rx = self.control_bus.clone_rx()?;
+        let shared = self.shared.clone();
+
+        let handle = std::thread::spawn(move || {
+            loop {
+                if rx.try_recv().is_ok() {
+                    break;
+                }
+                let stream = match listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to accept websocket connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let shared = shared.clone();
+                std::thread::spawn(move || shared.accept(stream));
+            }
+        });
+
+        Ok(handle)
+    }
+
+    fn connect(&self) -> Result<JoinHandle<()>, MCPError> {
+        let config = &self.shared.config;
+        let addr = format!("{}:{}", config.ip_address, config.port);
+        let url = format!("ws://{}{}", addr, config.endpoint);
+        let stream = TcpStream::connect(&addr)
+            .map_err(|e| MCPError::Transport(format!("Failed to connect to {}: {}", addr, e)))?;
+        let (ws, response) = tungstenite::client(url.as_str(), stream)
+            .map_err(|e| MCPError::Transport(format!("WebSocket handshake failed: {}", e)))?;
+
+        let session_id = response.headers().get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+        info!("WebSocket session {} connected to {}", session_id, url);
+
+        //registered before returning so messages sent right after start are queued
+        let outgoing = self.shared.register(&session_id);
+        let shared = self.shared.clone();
+        let handle = std::thread::spawn(move || {
+            if let Err(e) = shared.run(ws, session_id, outgoing) {
+                warn!("WebSocket connection failed: {}", e);
+            }
+        });
+        Ok(handle)
+    }
+
+    pub fn layer0_tx(&self, data: PayLoad) -> Result<(), MCPError> {
+        let session_id = data.ctx.as_ref()
+            .and_then(|ctx| ctx.data.get(SESSION_ID_KEY).cloned());
+        let data = data.data.ok_or_else(||
+            MCPError::Transport("Payload data is None".to_string()))?;
+
+        let connection = match session_id.and_then(|sid| self.shared.connections.get(&sid)) {
+            Some(connection) => Some(connection.value().clone()),
+            //a client has a single connection
+            None if !self.shared.is_server => self.shared.connections.iter().next().map(|c| c.value().clone()),
+            None => None,
+        };
+        let connection = connection.ok_or_else(||
+            MCPError::Transport("No websocket connection for session".to_string()))?;
+        connection.send(data)
+            .map_err(|_| MCPError::Transport("WebSocket connection closed".to_string()))
+    }
+
+    pub fn layer0_rx(&self) -> Result<PayLoad, MCPError> {
+        loop {
+            match self.inbound_rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(payload) => return Ok(payload),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.shared.closed.load(Ordering::Acquire) {
+                        return Err(MCPError::Closed("websocket".to_string()));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(MCPError::Closed("websocket".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl WsShared {
+    // the handshake callback signature is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    fn accept(&self, stream: TcpStream) {
+        let session_id = format!("{:032x}", rand::random::<u128>());
+        let endpoint = self.config.endpoint.clone();
+        let allowed_origins = self.config.allowed_origins.clone();
+        let issued = session_id.clone();
+
+        let _ = stream.set_nonblocking(false);
+        let callback = move |request: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+            if request.uri().path() != endpoint {
+                return Err(error_response(StatusCode::NOT_FOUND, "Not Found"));
+            }
+            let origin = request.headers().get("Origin").and_then(|o| o.to_str().ok());
+            if let (Some(allowed), Some(origin)) = (&allowed_origins, origin) {
+                if !allowed.iter().any(|o| o == origin) {
+                    return Err(error_response(StatusCode::FORBIDDEN, "Origin not allowed"));
+                }
+            }
+            if let Ok(value) = HeaderValue::from_str(&issued) {
+                response.headers_mut().insert(SESSION_HEADER, value);
+            }
+            Ok(response)
+        };
+
+        let ws = match tungstenite::accept_hdr(stream, callback) {
+            Ok(ws) => ws,
+            Err(e) => {
+                warn!("WebSocket handshake failed: {}", e);
+                return;
+            }
+        };
+        info!("WebSocket session {} connected", session_id);
+
+        let outgoing = self.register(&session_id);
+        if let Err(e) = self.run(ws, session_id, outgoing) {
+            warn!("WebSocket connection failed: {}", e);
+        }
+    }
+
+    fn register(&self, session_id: &str) -> Receiver<String> {
+        let (tx, outgoing) = unbounded();
+        self.connections.insert(session_id.to_string(), tx);
+        outgoing
+    }
+
+    /// Serve one connection: flush queued messages, keep the peer alive with
+    /// pings and forward text frames inbound until either side closes.
+    fn run(&self, mut ws: WebSocket<TcpStream>, session_id: String, outgoing: Receiver<String>) -> Result<(), MCPError> {
+        let mut stop = self.control_bus.clone_rx()?;
+
+        let result = self.pump(&mut ws, &session_id, &outgoing, &mut stop);
+
+        self.connections.remove(&session_id);
+        if self.is_server {
+            SESSION_STORE.invalidate_session(&session_id);
+        } else {
+            self.closed.store(true, Ordering::Release);
+        }
+        info!("WebSocket session {} closed", session_id);
+        result
+    }
+
+    fn pump(&self, ws: &mut WebSocket<TcpStream>, session_id: &str, outgoing: &Receiver<String>, stop: &mut BusReader<i32>) -> Result<(), MCPError> {
+        ws.get_ref().set_read_timeout(Some(POLL_INTERVAL))
+            .map_err(|e| MCPError::Transport(format!("Failed to set read timeout: {}", e)))?;
+
+        let mut last_ping = Instant::now();
+        let mut last_seen = Instant::now();
+        let mut closing = false;
+        loop {
+            if !closing && (stop.try_recv().is_ok() || self.closed.load(Ordering::Acquire)) {
+                //the peer answers with its own close frame, which ends the loop
+                closing = true;
+                ws.close(None).map_err(ws_error)?;
+            }
+
+            while let Ok(data) = outgoing.try_recv() {
+                ws.send(Message::text(data)).map_err(ws_error)?;
+            }
+
+            if last_ping.elapsed() >= KEEPALIVE_INTERVAL {
+                if last_seen.elapsed() >= KEEPALIVE_INTERVAL * 2 {
+                    return Err(MCPError::Transport("WebSocket peer stopped responding".to_string()));
+                }
+                ws.send(Message::Ping(Bytes::new())).map_err(ws_error)?;
+                last_ping = Instant::now();
+            }
+
+            match ws.read() {
+                Ok(Message::Text(text)) => {
+                    last_seen = Instant::now();
+                    self.push_inbound(session_id, text.to_string());
+                }
+                Ok(Message::Binary(_)) => {
+                    last_seen = Instant::now();
+                    warn!("Ignoring binary frame on websocket session {}", session_id);
+                }
+                Ok(_) => {
+                    last_seen = Instant::now();
+                }
+                Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(()),
+                Err(e) => return Err(ws_error(e)),
+            }
+        }
+    }
+
+    fn push_inbound(&self, session_id: &str, data: String) {
+        let mut ctx = ChainContext {
+            data: HashMap::new(),
+        };
+        ctx.data.insert(SESSION_ID_KEY.to_owned(), session_id.to_string());
+        let _ = self.inbound.send(PayLoad {
+            data: Some(data),
+            ctx: Some(ctx),
+        });
+    }
+}
+
+impl McpLayer for WebSocketTransport {
+    fn create(&self) -> SharedLayer {
+        let tx_io = self.clone();
+        let rx_io = self.clone();
+
+        let builder = LayerBuilder::new();
+        let layer = builder
+            .with_inbound_fn(move |_req| {
+                let data = rx_io.layer0_rx().map_err(super::inbound_error)?;
+                Ok(LayerResult {
+                    direction: Direction::Inbound,
+                    data: Some(data),
+                })
+            })
+            .with_outbound_fn(move |req| {
+                let req = req.ok_or_else(|| "no data to send".to_string())?;
+                if let Err(e) = tx_io.layer0_tx(req) {
+                    warn!("websocket transport failed to send: {}", e);
+                    return Err(e.to_string());
+                }
+                Ok(LayerResult {
+                    direction: Direction::Outbound,
+                    data: None,
+                })
+            }).build();
+        layer.unwrap()
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+fn ws_error(e: tungstenite::Error) -> MCPError {
+    MCPError::Transport(format!("WebSocket error: {}", e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::client::{Client, ClientProvider};
+    use crate::executor::{ClientExecutor, ServerExecutor};
+    use crate::schema::schema::{RequestId, LATEST_PROTOCOL_VERSION};
+    use crate::server::{Server, ServerConfig};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct NoopClientService;
+
+    impl ClientProvider for NoopClientService {
+        fn client_ping_response(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_list_roots(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_sampling_message(&self, _id: RequestId, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+
+        fn client_logs(&self, _params: Option<Value>) -> Result<(), MCPError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_websocket_transport() {
+        let transport = WebSocketTransport::new(HttpTransportConfig {
+            port: 0,
+            ..Default::default()
+        }, true);
+        let port = transport.local_addr().unwrap().port();
+        transport.start().unwrap();
+
+        let mut server = Server::new(ServerConfig::new());
+        server.add_transport_layer(transport.create());
+        let _ = server.start();
+        server.build();
+        let mut server_executor = ServerExecutor::new();
+        let _ = server_executor.start(server);
+
+        let client_transport = WebSocketTransport::new(HttpTransportConfig {
+            port,
+            ..Default::default()
+        }, false);
+        client_transport.start().unwrap();
+
+        let mut client = Client::<NoopClientService>::new();
+        let client = client.with_timeout(Duration::from_secs(5));
+        client.add_transport_layer(client_transport.create());
+        client.start().unwrap();
+        client.build();
+        let mut client_executor = ClientExecutor::new();
+        let _ = client_executor.start(client.clone());
+
+        let init_result = client.initialize().unwrap();
+        assert_eq!(init_result["protocolVersion"], LATEST_PROTOCOL_VERSION);
+        client.ping().unwrap();
+        assert_eq!(transport.connections(), 1);
+
+        //stop closes the connection on both ends
+        client_transport.stop().unwrap();
+        let start = Instant::now();
+        while transport.connections() > 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(transport.connections(), 0);
+        assert!(client_transport.layer0_rx().is_err());
+
+        client_executor.stop();
+        server_executor.stop();
+    }
+}